@@ -0,0 +1,212 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use teloxide::{prelude::*, types::InputFile};
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+
+use common::{DateTime, LogError, UnixDateTime};
+use db::AdminRepo;
+
+/// Backups are named `app-pulse-{time}.db`, see [`DateTime::format_compact`]
+const BACKUP_PREFIX: &str = "app-pulse-";
+const BACKUP_SUFFIX: &str = ".db";
+/// Telegram's limit of size of document, sent by bot
+const MAX_DOCUMENT_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Write backup of database to `dir` every `interval`, keeping `keep` newest
+/// backups. First backup is written `interval` after last existing backup,
+/// so restarts don't produce extra backups
+pub async fn start_backup_job<R: AdminRepo>(db: R, dir: PathBuf, interval: Duration, keep: usize) {
+    log::debug!("starting backups of db to {}", dir.display());
+    let last_backup_at = match list_backups(&dir).await {
+        Ok(backups) => backups.iter().map(|(time, _)| *time).max(),
+        Err(e) => {
+            log::warn!("failed to list backups in {}: {e}", dir.display());
+            None
+        }
+    };
+    let wait = last_backup_at.map_or(0, |t| t + interval.as_secs() as i64 - DateTime::now());
+    let start = Instant::now() + Duration::from_secs(wait.max(0) as u64);
+    let mut interval = interval_at(start, interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match write_backup(&db, &dir, keep).await {
+            Ok(path) => log::info!("db is backed up to {}", path.display()),
+            Err(e) => log::error!("failed to back up db: {e}"),
+        }
+    }
+}
+
+/// Write backup to temporary file and send it as document. File is removed
+/// after sending
+pub(crate) async fn send_backup<R: AdminRepo>(bot: &Bot, db: &R, chat_id: ChatId) -> Result<()> {
+    let path = std::env::temp_dir().join(backup_file_name(DateTime::now()));
+    let _ = tokio::fs::remove_file(&path).await;
+    let res = match write_private_backup(db, &path).await {
+        Ok(()) => send_document(bot, chat_id, &path).await,
+        Err(e) => Err(e),
+    };
+    tokio::fs::remove_file(&path)
+        .await
+        .log_error_msg("failed to remove sent backup");
+    res
+}
+
+/// Write backup to new file, which only owner can read, as temporary
+/// directory is readable by everyone
+async fn write_private_backup<R: AdminRepo>(db: &R, path: &Path) -> Result<()> {
+    // backup can be written into existing empty file. New file is required,
+    // so it's not a file or link, placed by someone else
+    tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .await?;
+    db.backup(&path.to_string_lossy()).await?;
+    Ok(())
+}
+
+async fn send_document(bot: &Bot, chat_id: ChatId, path: &Path) -> Result<()> {
+    let size = tokio::fs::metadata(path).await?.len();
+    if size > MAX_DOCUMENT_SIZE {
+        bail!("backup is too large to send: {size} bytes");
+    }
+    bot.send_document(chat_id, InputFile::file(path)).await?;
+    Ok(())
+}
+
+async fn write_backup<R: AdminRepo>(db: &R, dir: &Path, keep: usize) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(backup_file_name(DateTime::now()));
+    // unfinished backup, e.g. on shutdown, is not named as backup
+    let tmp_path = path.with_extension("tmp");
+    let _ = tokio::fs::remove_file(&tmp_path).await;
+    db.backup(&tmp_path.to_string_lossy()).await?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    for name in outdated_backups(list_backups(dir).await?, keep) {
+        log::debug!("removing old backup {name}");
+        tokio::fs::remove_file(dir.join(name)).await?;
+    }
+    Ok(path)
+}
+
+/// Names of backups in `dir` with their times. Other files are skipped
+async fn list_backups(dir: &Path) -> Result<Vec<(UnixDateTime, String)>> {
+    let mut backups = vec![];
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(backups),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(time) = backup_time(&name) {
+            backups.push((time, name));
+        }
+    }
+    Ok(backups)
+}
+
+/// Names of backups, which are older than `keep` newest ones
+fn outdated_backups(mut backups: Vec<(UnixDateTime, String)>, keep: usize) -> Vec<String> {
+    backups.sort_unstable_by(|a, b| b.cmp(a));
+    backups
+        .into_iter()
+        .skip(keep)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+fn backup_file_name(time: UnixDateTime) -> String {
+    format!(
+        "{BACKUP_PREFIX}{}{BACKUP_SUFFIX}",
+        DateTime::format_compact(time)
+    )
+}
+
+/// Time of backup from its file name, `None` if file is not a backup
+fn backup_time(name: &str) -> Option<UnixDateTime> {
+    name.strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_SUFFIX)
+        .and_then(DateTime::parse_compact)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use db::{DbOptions, UserRepo, DB};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_private_backup() -> Result<()> {
+        let db_path = "../../target/test_write_private_backup.db";
+        let path = Path::new("../../target/test_write_private_backup.backup.db");
+        let _ = tokio::fs::remove_file(db_path).await;
+        let _ = tokio::fs::remove_file(path).await;
+        let db = DB::init(db_path, &DbOptions::default()).await?;
+        db.add_user(db::models::User::builder().user_id(1).build())
+            .await?;
+
+        write_private_backup(&db, path).await?;
+        let mode = tokio::fs::metadata(path).await?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let backup = DB::init(&path.to_string_lossy(), &DbOptions::default()).await?;
+        assert!(backup.select_user(1).await?.is_some());
+        backup.close().await;
+
+        // existing file is not overwritten
+        assert!(write_private_backup(&db, path).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_file_name() {
+        let name = backup_file_name(1704110400);
+        assert_eq!(name, "app-pulse-20240101-120000.db");
+        for (i, (name, expected)) in [
+            (name.as_str(), Some(1704110400)),
+            ("app-pulse-20240101-120000.db-journal", None),
+            ("app-pulse.db", None),
+            ("notes.txt", None),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_eq!(backup_time(name), expected, "test table[{i}]");
+        }
+    }
+
+    #[test]
+    fn test_outdated_backups() {
+        let backups = [3, 1, 4, 2]
+            .into_iter()
+            .map(|t| (t, backup_file_name(t)))
+            .collect::<Vec<_>>();
+        for (i, (keep, expected)) in [
+            (5, vec![]),
+            (4, vec![]),
+            (2, vec![2, 1]),
+            (0, vec![4, 3, 2, 1]),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let expected = expected
+                .into_iter()
+                .map(backup_file_name)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                outdated_backups(backups.clone(), keep),
+                expected,
+                "test table[{i}]"
+            );
+        }
+    }
+}
@@ -1,24 +1,41 @@
 use teloxide::{
     payloads::SendMessageSetters,
     prelude::{Requester, ResponseResult},
-    types::Message,
+    types::{Message, ParseMode},
+    utils::markdown,
     Bot,
 };
 
-use db::{models::Stats, DB};
+use common::{admin_role, is_admin_chat_id, tg_truncate, DateTime, UnixDateTime};
+use db::{
+    models::{Stats, User},
+    AppRepo, Repository, SubscriptionRepo, UserRepo,
+};
 use i18n::{tr, tr_literal};
+use sources::{SourcesControl, Update};
 
 use crate::{
+    backup::send_backup,
     bot_messages::{get_help, get_user_lang},
+    broadcast::{broadcast_preview, parse_broadcast},
     commands::AdminCommand,
+    keyboards::Keyboards,
+    source_health::sources_health_text,
     utils::escape,
 };
 
-pub async fn admin_command_handler(
+/// How many users to show in /users
+const RECENT_USERS_LIMIT: u32 = 20;
+/// Max length of telegram message, see [`common::tg_len`]. Errors from
+/// sources can be longer
+pub(crate) const MAX_MESSAGE_LEN: usize = 4096;
+
+pub async fn admin_command_handler<R: Repository>(
     bot: Bot,
     msg: Message,
     cmd: AdminCommand,
-    db: DB,
+    db: R,
+    sources: SourcesControl,
 ) -> ResponseResult<()> {
     let user = db.select_user(msg.chat.id).await.ok().flatten();
     let lang = get_user_lang(user.as_ref(), msg.from.as_ref());
@@ -32,16 +49,159 @@ pub async fn admin_command_handler(
             }
             Err(e) => log::error!("failed to get stats: {e}"),
         },
-        AdminCommand::Help => {
-            bot.send_message(msg.chat.id, escape(get_help(&lang, true)))
-                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        AdminCommand::Broadcast(text) => {
+            let Some(texts) = parse_broadcast(&text) else {
+                bot.send_message(msg.chat.id, tr!(broadcast_usage, &lang))
+                    .await?;
+                return Ok(());
+            };
+            let broadcast_id = match db.add_broadcast(msg.chat.id.0, &texts).await {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("failed to save broadcast: {e}");
+                    return Ok(());
+                }
+            };
+            let recipients = match db.select_broadcast_recipients(broadcast_id).await {
+                Ok(users) => users.len(),
+                Err(e) => {
+                    log::error!("failed to select broadcast recipients: {e}");
+                    return Ok(());
+                }
+            };
+            bot.send_message(msg.chat.id, broadcast_preview(&texts, recipients, &lang))
+                .reply_markup(Keyboards::broadcast(broadcast_id, &lang))
+                .await?;
+        }
+        AdminCommand::User(user_ref) => {
+            if let Some(user) = find_user(&bot, &db, &msg, &user_ref, &lang).await? {
+                bot.send_message(msg.chat.id, user_info(&db, &user, &lang).await)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await?;
+            }
+        }
+        AdminCommand::Users => match db.select_recent_users(RECENT_USERS_LIMIT).await {
+            Ok(users) => {
+                let list = users
+                    .iter()
+                    .map(|u| {
+                        format!(
+                            "{} {}",
+                            u.display(),
+                            markdown::escape(&format!("- {}", format_time(u.joined_at(), &lang)))
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                bot.send_message(msg.chat.id, [tr!(users_header, &lang), list].join("\n\n"))
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await?;
+            }
+            Err(e) => log::error!("failed to select recent users: {e}"),
+        },
+        AdminCommand::Ban(user_ref) => {
+            handle_ban_command(&bot, &db, &msg, &user_ref, true, &lang).await?
+        }
+        AdminCommand::Unban(user_ref) => {
+            handle_ban_command(&bot, &db, &msg, &user_ref, false, &lang).await?
+        }
+        AdminCommand::Sources => match db.select_sources_health().await {
+            Ok(health) => {
+                bot.send_message(msg.chat.id, sources_health_text(&health, &lang))
+                    .await?;
+            }
+            Err(e) => log::error!("failed to select sources health: {e}"),
+        },
+        AdminCommand::Fetch(query) => {
+            let sent = bot
+                .send_message(msg.chat.id, tr!(fetch_started, &lang))
+                .await?;
+            let results = sources.fetch(&query).await;
+            let text = if results.is_empty() {
+                tr!(fetch_source_not_found, &lang, &sources.names().join(", "))
+            } else {
+                results
+                    .into_iter()
+                    .map(|(source, res)| match res {
+                        Ok(count) => tr!(fetch_result, &lang, source, &count.to_string()),
+                        Err(e) => tr!(fetch_failed, &lang, source, &e),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            let text = tg_truncate(&text, MAX_MESSAGE_LEN).to_string();
+            bot.edit_message_text(msg.chat.id, sent.id, text).await?;
+        }
+        AdminCommand::Preview(query) => {
+            let sent = bot
+                .send_message(msg.chat.id, tr!(fetch_started, &lang))
                 .await?;
+            let results = sources.preview(&query).await;
+            let text = if results.is_empty() {
+                tr!(fetch_source_not_found, &lang, &sources.names().join(", "))
+            } else {
+                results
+                    .into_iter()
+                    .map(|(source, res)| match res {
+                        Ok(updates) => std::iter::once(tr!(
+                            preview_header,
+                            &lang,
+                            source,
+                            &updates.len().to_string()
+                        ))
+                        .chain(updates.iter().map(|u| preview_update(u, &lang)))
+                        .collect::<Vec<_>>()
+                        .join("\n\n"),
+                        Err(e) => tr!(preview_failed, &lang, source, &e),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            };
+            let text = tg_truncate(&text, MAX_MESSAGE_LEN).to_string();
+            bot.edit_message_text(msg.chat.id, sent.id, text).await?;
+        }
+        AdminCommand::Backup => {
+            if let Err(e) = send_backup(&bot, &db, msg.chat.id).await {
+                log::error!("failed to send backup: {e}");
+                bot.send_message(msg.chat.id, tr!(backup_failed, &lang, &e.to_string()))
+                    .await?;
+            }
+        }
+        AdminCommand::Help => {
+            bot.send_message(
+                msg.chat.id,
+                escape(get_help(&lang, admin_role(msg.chat.id.0))),
+            )
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
         }
     }
 
     Ok(())
 }
 
+/// Reply to admin, whose role is not enough for command
+pub async fn admin_forbidden_handler<R: UserRepo>(
+    bot: Bot,
+    msg: Message,
+    cmd: AdminCommand,
+    db: R,
+) -> ResponseResult<()> {
+    let user = db.select_user(msg.chat.id).await.ok().flatten();
+    let lang = get_user_lang(user.as_ref(), msg.from.as_ref());
+    log::warn!(
+        "admin {} tried to run command, which requires {:?}",
+        msg.chat.id,
+        cmd.required_role()
+    );
+    bot.send_message(
+        msg.chat.id,
+        tr!(admin_command_forbidden, &lang, cmd.required_role().as_str()),
+    )
+    .await?;
+    Ok(())
+}
+
 fn translate_stats(stats: &Stats, lang: &str) -> String {
     [
         tr!(stats_header, lang),
@@ -59,3 +219,202 @@ fn translate_stats(stats: &Stats, lang: &str) -> String {
     ]
     .join("\n")
 }
+
+/// Reference to user in admin commands
+#[derive(Debug, PartialEq, Eq)]
+enum UserRef<'a> {
+    Id(i64),
+    Username(&'a str),
+}
+
+impl<'a> UserRef<'a> {
+    fn parse(s: &'a str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(username) = s.strip_prefix('@') {
+            return (!username.is_empty()).then_some(Self::Username(username));
+        }
+        s.parse().ok().map(Self::Id)
+    }
+}
+
+/// Find user by reference from command, replying to admin if not found
+async fn find_user<R: UserRepo>(
+    bot: &Bot,
+    db: &R,
+    msg: &Message,
+    user_ref: &str,
+    lang: &str,
+) -> ResponseResult<Option<User>> {
+    let Some(user_ref) = UserRef::parse(user_ref) else {
+        bot.send_message(msg.chat.id, tr!(user_usage, lang)).await?;
+        return Ok(None);
+    };
+    let res = match user_ref {
+        UserRef::Id(id) => db.select_user(db::types::ChatId(id)).await,
+        UserRef::Username(username) => db.select_user_by_username(username).await,
+    };
+    match res {
+        Ok(Some(user)) => Ok(Some(user)),
+        Ok(None) => {
+            bot.send_message(msg.chat.id, tr!(user_not_found, lang))
+                .await?;
+            Ok(None)
+        }
+        Err(e) => {
+            log::error!("failed to select user: {e}");
+            Ok(None)
+        }
+    }
+}
+
+async fn handle_ban_command<R: UserRepo>(
+    bot: &Bot,
+    db: &R,
+    msg: &Message,
+    user_ref: &str,
+    banned: bool,
+    lang: &str,
+) -> ResponseResult<()> {
+    let Some(user) = find_user(bot, db, msg, user_ref, lang).await? else {
+        return Ok(());
+    };
+    if banned && is_admin_chat_id(user.user_id()) {
+        bot.send_message(msg.chat.id, tr!(user_ban_admin, lang))
+            .await?;
+        return Ok(());
+    }
+    if let Err(e) = db.save_user_banned(user.user_id(), banned).await {
+        log::error!("failed to save user banned: {e}");
+        return Ok(());
+    }
+    log::info!("user {} banned: {banned}", user.user_id());
+
+    let text = if banned {
+        tr!(user_banned, lang, &user.display())
+    } else {
+        tr!(user_unbanned, lang, &user.display())
+    };
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+/// Everything known about user, formatted as markdown
+async fn user_info<R: AppRepo + SubscriptionRepo>(db: &R, user: &User, lang: &str) -> String {
+    let yes_no = |v: bool| {
+        if v {
+            tr!(user_info_yes, lang)
+        } else {
+            tr!(user_info_no, lang)
+        }
+    };
+    let or_none = |list: Vec<String>| {
+        if list.is_empty() {
+            tr!(user_info_none, lang)
+        } else {
+            list.join(", ")
+        }
+    };
+
+    let subscriptions = db
+        .select_user_subscriptions(user.user_id())
+        .await
+        .map(|subs| {
+            subs.iter()
+                .filter(|s| s.subscribed())
+                .map(|s| s.source().display_name())
+                .collect()
+        })
+        .unwrap_or_else(|e| {
+            log::error!("failed to select user subscriptions: {e}");
+            vec![]
+        });
+    let apps = db
+        .select_user_followed_apps(user.user_id())
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("failed to select user apps: {e}");
+            vec![]
+        });
+    let settings = format!(
+        "{}, {}, {}",
+        user.verbosity().as_str(),
+        user.delivery_mode().as_str(),
+        DateTime::format_utc_offset(user.utc_offset())
+    );
+
+    let fields = [
+        ("user-info-id", user.user_id().to_string()),
+        ("user-info-lang", user.lang().to_string()),
+        ("user-info-joined", format_time(user.joined_at(), lang)),
+        (
+            "user-info-last-notified",
+            format_time(user.last_notified_at(), lang),
+        ),
+        ("user-info-bot-blocked", yes_no(user.bot_blocked())),
+        ("user-info-banned", yes_no(user.banned())),
+        ("user-info-settings", settings),
+        ("user-info-subscriptions", or_none(subscriptions)),
+        ("user-info-apps", or_none(apps)),
+    ]
+    .into_iter()
+    .map(|(tr_key, value)| markdown::escape(&format!("{}: {value}", tr_literal!(tr_key, lang))))
+    .collect::<Vec<_>>()
+    .join("\n");
+
+    [
+        format!("{} {}", tr!(user_info_header, lang), user.display()),
+        fields,
+    ]
+    .join("\n\n")
+}
+
+/// Everything known about update from source
+fn preview_update(update: &Update, lang: &str) -> String {
+    let mut lines = vec![
+        update.app_id().to_string(),
+        tr!(
+            preview_update_time,
+            lang,
+            &DateTime::format(update.update_time())
+        ),
+    ];
+    if let Some(description) = update.description() {
+        lines.push(description.to_string());
+    }
+    if let Some(link) = update.description_link() {
+        lines.push(format!("{} {link}", tr!(update_description_link, lang)));
+    }
+    if let Some(link) = update.update_link() {
+        lines.push(format!("{} {link}", tr!(update_link, lang)));
+    }
+    lines.join("\n")
+}
+
+pub(crate) fn format_time(time: UnixDateTime, lang: &str) -> String {
+    if time == 0 {
+        tr!(user_info_unknown, lang)
+    } else {
+        DateTime::format(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_user_ref() {
+        let table = [
+            ("123", Some(UserRef::Id(123))),
+            (" @some_user ", Some(UserRef::Username("some_user"))),
+            ("@", None),
+            ("", None),
+            ("some_user", None),
+        ];
+        for (i, (s, expected)) in table.into_iter().enumerate() {
+            assert_eq!(UserRef::parse(s), expected, "test table[{i}]");
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use reqwest::Url;
 use teloxide::{
     prelude::*,
@@ -7,23 +9,37 @@ use teloxide::{
     },
 };
 
-use db::{models::ShouldNotify, DB};
+use common::{has_admin_role, AdminRole, LogError};
+use db::{
+    models::{BroadcastStatus, ShouldNotify},
+    types::Id,
+    AppRepo, Repository, UserRepo,
+};
 
 use crate::{
+    broadcast::BroadcastQueue,
     callback::{Callback, CallbackParseError},
-    keyboards::{Keyboards, LanguagesKeyboardToken, NewAppKeyboardKind},
+    keyboards::{Keyboards, LanguagesKeyboardToken, NewAppKeyboardKind, SettingsPage},
+    settings::{
+        settings_page, SettingsDialogue, SettingsStorage, SettingsValues, MAX_UTC_OFFSET,
+        MIN_UTC_OFFSET,
+    },
     tr, DEFAULT_USER_LANG,
 };
 
-pub async fn callback_handler(bot: Bot, q: CallbackQuery, db: DB) -> ResponseResult<()> {
+pub async fn callback_handler<R: Repository>(
+    bot: Bot,
+    q: CallbackQuery,
+    db: R,
+    storage: Arc<SettingsStorage<R>>,
+    broadcasts: BroadcastQueue,
+) -> ResponseResult<()> {
     let answer_err = bot.answer_callback_query(q.id.clone()).show_alert(true);
     let chat_id = q.from.id;
 
-    let lang = db
-        .select_user(chat_id)
-        .await
-        .ok()
-        .flatten()
+    let user = db.select_user(chat_id).await.ok().flatten();
+    let lang = user
+        .as_ref()
         .map(|u| u.lang().to_string())
         .unwrap_or(DEFAULT_USER_LANG.to_string());
 
@@ -39,6 +55,7 @@ pub async fn callback_handler(bot: Bot, q: CallbackQuery, db: DB) -> ResponseRes
     let callback = match Callback::try_from(&data) {
         Ok(c) => c,
         Err(e) => {
+            metrics::counter!("callback_queries_total", "type" => "invalid").increment(1);
             let msg = match e {
                 CallbackParseError::InvalidCallback => {
                     log::error!("invalid callback: {data:?}");
@@ -58,52 +75,268 @@ pub async fn callback_handler(bot: Bot, q: CallbackQuery, db: DB) -> ResponseRes
         }
     };
 
-    match callback {
+    metrics::counter!("callback_queries_total", "type" => callback.kind()).increment(1);
+
+    // settings page, which should be shown after changing setting
+    let (changed_page, res) = match callback {
         Callback::Notify {
+            source_id,
             app_id,
             should_notify,
         } => {
-            let res = handle_update_callback(should_notify, db, chat_id, &app_id, &lang).await;
+            let res =
+                handle_update_callback(should_notify, &db, chat_id, source_id, &app_id, &lang)
+                    .await;
             match res {
                 Ok((popup_msg, keyboard_kind)) => {
                     bot.answer_callback_query(q.id).text(popup_msg).await?;
-                    edit_update_msg(q.message, bot, chat_id, &app_id, keyboard_kind, &lang).await?;
+                    edit_update_msg(
+                        q.message,
+                        bot,
+                        chat_id,
+                        source_id,
+                        &app_id,
+                        keyboard_kind,
+                        &lang,
+                    )
+                    .await?;
                 }
                 Err(Some(e)) => {
                     answer_err.text(e).await?;
                 }
                 _ => (),
             }
+            return Ok(());
         }
-        Callback::SetLang { lang, token } => match handle_lang_callback(db, chat_id, &lang).await {
-            Ok(popup_msg) => {
-                bot.answer_callback_query(q.id).text(popup_msg).await?;
-                let (text, markup) = match token {
-                    LanguagesKeyboardToken::Start => (tr!(welcome_suggest_subscribe, &lang), None),
-                    LanguagesKeyboardToken::Settings => (
-                        tr!(choose_language, &lang),
-                        Some(Keyboards::languages(token)),
-                    ),
-                };
-                edit_msg_text(q.message, bot, chat_id, text, markup).await?;
+        Callback::SetLang { lang, token } => {
+            match handle_lang_callback(&db, chat_id, &lang).await {
+                Ok(popup_msg) => {
+                    bot.answer_callback_query(q.id).text(popup_msg).await?;
+                    let (text, markup) = match token {
+                        LanguagesKeyboardToken::Start => {
+                            (tr!(welcome_suggest_subscribe, &lang), None)
+                        }
+                        LanguagesKeyboardToken::Settings => {
+                            let values = SettingsValues::load(&db, user.as_ref(), chat_id).await;
+                            let (text, markup) =
+                                settings_page(SettingsPage::Language, &values, &lang);
+                            (text, Some(markup))
+                        }
+                    };
+                    edit_msg_text(q.message, bot, chat_id, text, markup).await?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    answer_err.text(e).await?;
+                    return Ok(());
+                }
             }
-            Err(e) => {
-                answer_err.text(e).await?;
+        }
+        Callback::Settings(navigation) => {
+            bot.answer_callback_query(q.id).await?;
+            let Some(message_id) = q.message.as_ref().map(|m| m.id()) else {
+                log::error!("got settings callback from user {chat_id} without message");
+                return Ok(());
+            };
+
+            let dialogue = SettingsDialogue::new(storage, chat_id.into());
+            let state = dialogue
+                .get()
+                .await
+                .log_error_msg("failed to get settings state")
+                .as_ref()
+                .ok()
+                .cloned()
+                .flatten()
+                .unwrap_or_default()
+                .navigate(navigation, message_id.0);
+
+            match state.page() {
+                Some(page) => {
+                    let values = SettingsValues::load(&db, user.as_ref(), chat_id).await;
+                    let (text, markup) = settings_page(page, &values, &lang);
+                    edit_msg_text(q.message, bot, chat_id, text, Some(markup)).await?;
+                }
+                None => {
+                    edit_msg_text(
+                        q.message,
+                        bot,
+                        chat_id,
+                        tr!(settings_closed, &lang),
+                        None::<InlineKeyboardMarkup>,
+                    )
+                    .await?;
+                }
+            }
+            dialogue
+                .update(state)
+                .await
+                .log_error_msg("failed to save settings state");
+            return Ok(());
+        }
+        Callback::Broadcast {
+            broadcast_id,
+            confirm,
+        } => {
+            if !has_admin_role(chat_id.0 as i64, AdminRole::Owner) {
+                log::error!("user {chat_id} tried to handle broadcast {broadcast_id}");
+                answer_err
+                    .text(tr!(something_wrong_invalid_callback, &lang))
+                    .await?;
+                return Ok(());
             }
-        },
+            let to = if confirm {
+                BroadcastStatus::Running
+            } else {
+                BroadcastStatus::Cancelled
+            };
+            let text = match db
+                .save_broadcast_status(broadcast_id, BroadcastStatus::Draft, to)
+                .await
+            {
+                Ok(true) if confirm => {
+                    broadcasts.push(broadcast_id).await;
+                    tr!(broadcast_confirmed, &lang)
+                }
+                Ok(true) => tr!(broadcast_cancelled, &lang),
+                Ok(false) => tr!(broadcast_already_handled, &lang),
+                Err(e) => {
+                    log::error!("failed to save broadcast status: {e}");
+                    answer_err
+                        .text(tr!(something_wrong_try_again, &lang))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            bot.answer_callback_query(q.id).text(text).await?;
+            // remove confirmation buttons
+            if let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) {
+                bot.edit_message_reply_markup(chat_id, message.id).await?;
+            }
+            return Ok(());
+        }
+        Callback::DeleteMe { confirm } => {
+            let text = if !confirm {
+                tr!(deleteme_cancelled, &lang)
+            } else {
+                match db.delete_user(chat_id).await {
+                    Ok(deleted) => {
+                        if deleted {
+                            log::info!(tg = true, event = "user_deleted", user_id = chat_id.0; "User {chat_id} deleted their data");
+                        }
+                        tr!(deleteme_done, &lang)
+                    }
+                    Err(e) => {
+                        log::error!("failed to delete user {chat_id}: {e}");
+                        answer_err
+                            .text(tr!(something_wrong_try_again, &lang))
+                            .await?;
+                        return Ok(());
+                    }
+                }
+            };
+            bot.answer_callback_query(q.id).await?;
+            edit_msg_text(q.message, bot, chat_id, text, None::<InlineKeyboardMarkup>).await?;
+            return Ok(());
+        }
+        Callback::SetVerbosity { verbosity } => (
+            SettingsPage::Verbosity,
+            save_setting(
+                db.save_user_verbosity(chat_id, verbosity).await,
+                "verbosity",
+                tr!(verbosity_saved, &lang),
+                &lang,
+            ),
+        ),
+        Callback::SetDelivery { mode } => (
+            SettingsPage::Delivery,
+            save_setting(
+                db.save_user_delivery_mode(chat_id, mode).await,
+                "delivery mode",
+                tr!(delivery_saved, &lang),
+                &lang,
+            ),
+        ),
+        Callback::ShiftTimezone { delta } => {
+            let current = user.as_ref().map(|u| u.utc_offset()).unwrap_or_default();
+            let utc_offset = (current + delta).clamp(MIN_UTC_OFFSET, MAX_UTC_OFFSET);
+            (
+                SettingsPage::Timezone,
+                save_setting(
+                    db.save_user_utc_offset(chat_id, utc_offset).await,
+                    "timezone",
+                    tr!(timezone_saved, &lang),
+                    &lang,
+                ),
+            )
+        }
+        Callback::Subscribe {
+            source_id,
+            subscribe,
+        } => (
+            SettingsPage::Sources,
+            save_setting(
+                db.save_user_subscribed(chat_id, source_id, subscribe).await,
+                "subscription",
+                if subscribe {
+                    tr!(subscribed, &lang)
+                } else {
+                    tr!(unsubscribed, &lang)
+                },
+                &lang,
+            ),
+        ),
+        Callback::SetNewSources { mode } => (
+            SettingsPage::Sources,
+            save_setting(
+                db.save_user_new_sources(chat_id, mode).await,
+                "new sources mode",
+                tr!(new_sources_saved, &lang),
+                &lang,
+            ),
+        ),
+    };
+
+    match res {
+        Ok(popup_msg) => {
+            bot.answer_callback_query(q.id).text(popup_msg).await?;
+            // reload values to show saved ones
+            let user = db.select_user(chat_id).await.ok().flatten();
+            let values = SettingsValues::load(&db, user.as_ref(), chat_id).await;
+            let (text, markup) = settings_page(changed_page, &values, &lang);
+            edit_msg_text(q.message, bot, chat_id, text, Some(markup)).await?;
+        }
+        Err(e) => {
+            answer_err.text(e).await?;
+        }
     }
 
     Ok(())
 }
 
+/// Convert result of saving setting to popup message
+fn save_setting(
+    res: Result<(), db::Error>,
+    setting: &str,
+    popup_msg: String,
+    lang: &str,
+) -> Result<String, String> {
+    res.map_err(|e| {
+        log::error!("failed to update {setting} for user: {e}");
+        tr!(something_wrong_try_again, lang)
+    })?;
+    Ok(popup_msg)
+}
+
 async fn handle_update_callback(
     should_notify: ShouldNotify,
-    db: DB,
+    db: &impl AppRepo,
     chat_id: UserId,
+    source_id: Id,
     app_id: &str,
     lang: &str,
 ) -> Result<(String, NewAppKeyboardKind), Option<String>> {
-    db.save_should_notify_user(chat_id, app_id, should_notify)
+    db.save_should_notify_user(chat_id, source_id, app_id, should_notify)
         .await
         .map_err(|e| {
             log::error!("failed to save user should_notify: {e}");
@@ -126,7 +359,11 @@ async fn handle_update_callback(
     Ok((popup_msg, keyboard_kind))
 }
 
-async fn handle_lang_callback(db: DB, chat_id: UserId, lang: &str) -> Result<String, String> {
+async fn handle_lang_callback(
+    db: &impl UserRepo,
+    chat_id: UserId,
+    lang: &str,
+) -> Result<String, String> {
     db.save_user_lang(chat_id, lang).await.map_err(|e| {
         log::error!("failed to update lang for user: {e}");
         tr!(something_wrong_try_again, lang)
@@ -161,6 +398,7 @@ async fn edit_update_msg(
     msg: Option<MaybeInaccessibleMessage>,
     bot: Bot,
     chat_id: UserId,
+    source_id: Id,
     app_id: &str,
     keyboard_kind: NewAppKeyboardKind,
     lang: &str,
@@ -169,6 +407,7 @@ async fn edit_update_msg(
         bot.edit_message_reply_markup(chat_id, id)
             .reply_markup(
                 Keyboards::update(
+                    source_id,
                     app_id,
                     extract_url_from_callback_msg(kind),
                     keyboard_kind,
@@ -200,3 +439,55 @@ fn extract_url_from_callback_msg(kind: MessageKind) -> Option<Url> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use db::memory::MemoryDb;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handle_update_callback() {
+        let db = MemoryDb::new();
+        let chat_id = UserId(1);
+        for (i, (should_notify, expected)) in [
+            (
+                ShouldNotify::Notify,
+                Some(NewAppKeyboardKind::NotifyEnabled),
+            ),
+            (
+                ShouldNotify::Ignore,
+                Some(NewAppKeyboardKind::NotifyDisabled),
+            ),
+            (ShouldNotify::Unspecified, None),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let res = handle_update_callback(should_notify, &db, chat_id, 1, "app", "en").await;
+            assert_eq!(res.ok().map(|(_, kind)| kind), expected, "test table[{i}]");
+            assert_eq!(
+                db.should_notify_user(chat_id, 1, "app").await.unwrap(),
+                should_notify,
+                "test table[{i}]"
+            );
+        }
+        // app with same id in other source is not affected
+        assert_eq!(
+            db.should_notify_user(chat_id, 2, "app").await.unwrap(),
+            ShouldNotify::Unspecified
+        );
+        assert_eq!(
+            db.select_user_followed_apps(chat_id).await.unwrap(),
+            Vec::<String>::new()
+        );
+
+        handle_update_callback(ShouldNotify::Notify, &db, chat_id, 1, "app", "en")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.select_user_followed_apps(chat_id).await.unwrap(),
+            ["app"]
+        );
+    }
+}
@@ -1,20 +1,25 @@
 use std::{
     collections::HashMap,
-    sync::{LazyLock, Mutex},
+    sync::{Arc, LazyLock, Mutex},
 };
 
 use teloxide::{
     prelude::*,
-    types::{BotCommand, ChatKind, MessageKind},
+    types::{BotCommand, ChatKind, InputFile, MessageKind},
 };
 
-use db::{models::User, types, DB};
+use common::{tg_len, AdminRole, LogError};
+use db::{models::User, types, Repository, UserRepo};
 
 use crate::{
+    bot_admin_messages::MAX_MESSAGE_LEN,
     commands::AdminCommand,
-    keyboards::{Keyboards, LanguagesKeyboardToken},
+    keyboards::{Keyboards, LanguagesKeyboardToken, SettingsPage},
+    settings::{
+        settings_page, Navigation, SettingsDialogue, SettingsState, SettingsStorage, SettingsValues,
+    },
     tr,
-    user::get_chat_name,
+    user::{get_chat_name, user_data_text},
     utils::escape,
     Command, DEFAULT_USER_LANG,
 };
@@ -22,11 +27,11 @@ use crate::{
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct HelpCacheKey {
     lang: String,
-    admin: bool,
+    admin: Option<AdminRole>,
 }
 
 impl HelpCacheKey {
-    fn new(lang: &str, admin: bool) -> Self {
+    fn new(lang: &str, admin: Option<AdminRole>) -> Self {
         Self {
             lang: lang.to_owned(),
             admin,
@@ -37,7 +42,13 @@ impl HelpCacheKey {
 static HELP_CACHE: LazyLock<Mutex<HashMap<HelpCacheKey, String>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
-pub async fn command_handler(bot: Bot, msg: Message, cmd: Command, db: DB) -> ResponseResult<()> {
+pub async fn command_handler<R: Repository>(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    db: R,
+    storage: Arc<SettingsStorage<R>>,
+) -> ResponseResult<()> {
     let user = db.select_user(msg.chat.id).await.ok().flatten();
     let lang = get_user_lang(user.as_ref(), msg.from.as_ref());
 
@@ -49,15 +60,21 @@ pub async fn command_handler(bot: Bot, msg: Message, cmd: Command, db: DB) -> Re
 
     match cmd {
         Command::Start => handle_start_command(bot.clone(), &db, user, &lang, msg).await?,
-        Command::Subscribe => match db.save_user_subscribed(msg.chat.id, true).await {
-            Ok(()) => {
-                bot.send_message(msg.chat.id, tr!(subscribed, &lang))
-                    .await?;
-                log::debug!("user {} subscribed", msg.chat.id);
-            }
-            Err(e) => log::error!("failed to subscribe user {}: {e}", msg.chat.id.0),
-        },
-        Command::Unsubscribe => match db.save_user_subscribed(msg.chat.id, false).await {
+        Command::Subscribe => {
+            let values = SettingsValues::load(&db, user.as_ref(), msg.chat.id).await;
+            let (text, markup) = settings_page(SettingsPage::Sources, &values, &lang);
+            let sent = bot
+                .send_message(msg.chat.id, text)
+                .reply_markup(markup)
+                .await?;
+            let state = SettingsState::opened(sent.id.0)
+                .navigate(Navigation::Open(SettingsPage::Sources), sent.id.0);
+            SettingsDialogue::new(storage, msg.chat.id)
+                .update(state)
+                .await
+                .log_error_msg("failed to save settings state");
+        }
+        Command::Unsubscribe => match db.save_user_subscribed_all(msg.chat.id, false).await {
             Ok(()) => {
                 bot.send_message(msg.chat.id, tr!(unsubscribed, &lang))
                     .await?;
@@ -71,19 +88,65 @@ pub async fn command_handler(bot: Bot, msg: Message, cmd: Command, db: DB) -> Re
                 .await?;
         }
         Command::Settings => {
-            bot.send_message(msg.chat.id, tr!(choose_language, &lang))
-                .reply_markup(Keyboards::languages(LanguagesKeyboardToken::Settings))
+            let values = SettingsValues::load(&db, user.as_ref(), msg.chat.id).await;
+            let (text, markup) = settings_page(SettingsPage::Main, &values, &lang);
+            let sent = bot
+                .send_message(msg.chat.id, text)
+                .reply_markup(markup)
                 .await?;
+            SettingsDialogue::new(storage, msg.chat.id)
+                .update(SettingsState::opened(sent.id.0))
+                .await
+                .log_error_msg("failed to save settings state");
         }
         Command::About => {
             bot.send_message(msg.chat.id, tr!(about_description, &lang))
                 .await?;
         }
         Command::Help => {
-            bot.send_message(msg.chat.id, escape(get_help(&lang, false)))
+            bot.send_message(msg.chat.id, escape(get_help(&lang, None)))
                 .parse_mode(teloxide::types::ParseMode::MarkdownV2)
                 .await?;
         }
+        Command::MyData => {
+            let Some(user) = user else {
+                bot.send_message(msg.chat.id, tr!(mydata_empty, &lang))
+                    .await?;
+                return Ok(());
+            };
+            let settings = SettingsDialogue::new(storage, msg.chat.id)
+                .get()
+                .await
+                .log_error_msg("failed to get settings state")
+                .as_ref()
+                .ok()
+                .cloned()
+                .flatten();
+            match user_data_text(&db, &user, settings.as_ref(), &lang).await {
+                Ok(text) if tg_len(&text) <= MAX_MESSAGE_LEN => {
+                    bot.send_message(msg.chat.id, text).await?;
+                }
+                Ok(text) => {
+                    let file = InputFile::memory(text).file_name("mydata.txt");
+                    bot.send_document(msg.chat.id, file).await?;
+                }
+                Err(e) => {
+                    log::error!("failed to collect data of user {}: {e}", msg.chat.id);
+                    bot.send_message(msg.chat.id, tr!(something_wrong_try_again, &lang))
+                        .await?;
+                }
+            }
+        }
+        Command::DeleteMe => {
+            if user.is_none() {
+                bot.send_message(msg.chat.id, tr!(mydata_empty, &lang))
+                    .await?;
+                return Ok(());
+            }
+            bot.send_message(msg.chat.id, tr!(deleteme_confirm, &lang))
+                .reply_markup(Keyboards::delete_me(&lang))
+                .await?;
+        }
     };
 
     Ok(())
@@ -91,7 +154,7 @@ pub async fn command_handler(bot: Bot, msg: Message, cmd: Command, db: DB) -> Re
 
 async fn handle_start_command(
     bot: Bot,
-    db: &DB,
+    db: &impl UserRepo,
     user: Option<User>,
     lang: &str,
     msg: Message,
@@ -99,7 +162,7 @@ async fn handle_start_command(
     match user {
         Some(u) => {
             if u.bot_blocked() {
-                log::info!(tg = true; "User {} returned", u.display());
+                log::info!(tg = true, event = "user_returned", user_id = u.user_id(); "User {} returned", u.display());
                 if let Err(e) = db.save_user_unavailable(u.user_id(), false).await {
                     log::error!("failed to save that user is returned: {e}")
                 }
@@ -124,7 +187,7 @@ async fn handle_start_command(
             match db.add_user(user).await {
                 Ok(()) => {
                     send_welcome_msg(bot.clone(), msg.chat.id, lang).await?;
-                    log::debug!("user {} saved", msg.chat.id);
+                    log::info!(event = "user_joined", user_id = msg.chat.id.0; "User {} joined", msg.chat.id);
                 }
                 Err(e) => log::error!("failed to save user {}: {e}", msg.chat.id.0),
             }
@@ -133,7 +196,7 @@ async fn handle_start_command(
     Ok(())
 }
 
-pub async fn message_handler(bot: Bot, msg: Message, db: DB) -> ResponseResult<()> {
+pub async fn message_handler<R: UserRepo>(bot: Bot, msg: Message, db: R) -> ResponseResult<()> {
     if msg.text().is_some_and(|m| m.starts_with("/")) {
         log::debug!("ignoring command sent to other bot");
         return Ok(());
@@ -151,10 +214,11 @@ pub async fn message_handler(bot: Bot, msg: Message, db: DB) -> ResponseResult<(
     Ok(())
 }
 
-pub(crate) fn get_help(lang: &str, admin: bool) -> String {
+/// Help with commands. Admin commands are listed for `admin` role
+pub(crate) fn get_help(lang: &str, admin: Option<AdminRole>) -> String {
     let key = HelpCacheKey::new(lang, admin);
 
-    log::debug!("sending help, admin = {admin}");
+    log::debug!("sending help, admin = {admin:?}");
 
     HELP_CACHE
         .lock()
@@ -164,7 +228,7 @@ pub(crate) fn get_help(lang: &str, admin: bool) -> String {
         .to_owned()
 }
 
-fn make_help(lang: &str, admin: bool) -> String {
+fn make_help(lang: &str, admin: Option<AdminRole>) -> String {
     fn build_commands(commands: impl IntoIterator<Item = BotCommand>) -> String {
         commands
             .into_iter()
@@ -177,12 +241,12 @@ fn make_help(lang: &str, admin: bool) -> String {
         tr!(commands_list_header, lang),
         "".to_string(),
         build_commands(Command::bot_commands_translated(lang)),
-        if admin {
+        if let Some(role) = admin {
             [
                 "".to_string(),
                 tr!(admin_commands_header, lang),
                 "".to_string(),
-                build_commands(AdminCommand::bot_commands_translated(lang)),
+                build_commands(AdminCommand::bot_commands_translated(role, lang)),
                 "".to_string(),
             ]
             .join("\n")
@@ -195,7 +259,7 @@ fn make_help(lang: &str, admin: bool) -> String {
 
 async fn send_welcome_msg(bot: Bot, chat_id: ChatId, lang: &str) -> ResponseResult<()> {
     bot.send_message(chat_id, tr!(welcome_choose_language, lang))
-        .reply_markup(Keyboards::languages(LanguagesKeyboardToken::Start))
+        .reply_markup(Keyboards::languages(LanguagesKeyboardToken::Start, lang))
         .await?;
     Ok(())
 }
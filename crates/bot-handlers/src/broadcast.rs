@@ -0,0 +1,334 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use teloxide::{prelude::*, RequestError};
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    time::{self, Instant, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+
+use common::LogError;
+use db::{
+    models::{
+        BroadcastProgress, BroadcastStatus, BroadcastText, DeliveryMode, DeliveryStatus, User,
+    },
+    types::Id,
+    BroadcastRepo, UserRepo,
+};
+use i18n::Localize;
+
+use crate::{
+    tr,
+    updates_notify::{
+        handle_bot_blocked, record_notification, ChatUnavailableError, MapBotBlockedError,
+        UpdateError,
+    },
+    DEFAULT_USER_LANG,
+};
+
+/// Telegram allows about 30 messages per second to different users
+const SEND_INTERVAL: Duration = Duration::from_millis(50);
+/// How often to report progress to admin
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Queue of confirmed broadcasts, which are sent by [`start_broadcast_job`]
+#[derive(Debug, Clone)]
+pub struct BroadcastQueue(Sender<Id>);
+
+impl BroadcastQueue {
+    pub fn new(sender: Sender<Id>) -> Self {
+        Self(sender)
+    }
+    pub(crate) async fn push(&self, broadcast_id: Id) {
+        self.0
+            .send(broadcast_id)
+            .await
+            .log_error_msg("failed to queue broadcast");
+    }
+}
+
+/// Send confirmed broadcasts, until channel is closed. Broadcasts, interrupted
+/// by restart or by cancelling `token`, are resumed from users, who didn't
+/// receive them yet
+pub async fn start_broadcast_job<R: BroadcastRepo + UserRepo>(
+    bot: Bot,
+    db: R,
+    mut rx: Receiver<Id>,
+    token: CancellationToken,
+) {
+    match db.select_running_broadcasts().await {
+        Ok(broadcasts) => {
+            for b in broadcasts {
+                log::info!("resuming broadcast {}", b.broadcast_id());
+                run_broadcast(&bot, &db, b.broadcast_id(), &token)
+                    .await
+                    .log_error_msg("failed to run broadcast");
+            }
+        }
+        Err(e) => log::error!("failed to select running broadcasts: {e}"),
+    }
+
+    log::debug!("starting listen for broadcasts");
+    while let Some(broadcast_id) = rx.recv().await {
+        run_broadcast(&bot, &db, broadcast_id, &token)
+            .await
+            .log_error_msg("failed to run broadcast");
+    }
+}
+
+/// Send broadcast, which should be running, to users, who didn't receive it
+/// yet. Stops when `token` is cancelled, then broadcast is resumed by
+/// [`start_broadcast_job`]
+pub async fn run_broadcast<R: BroadcastRepo + UserRepo>(
+    bot: &Bot,
+    db: &R,
+    broadcast_id: Id,
+    token: &CancellationToken,
+) -> Result<()> {
+    let Some(broadcast) = db.select_broadcast(broadcast_id).await? else {
+        log::error!("broadcast {broadcast_id} not found");
+        return Ok(());
+    };
+    if broadcast.status() != BroadcastStatus::Running {
+        log::debug!("broadcast {broadcast_id} is not running, skipping");
+        return Ok(());
+    }
+
+    let texts = db.select_broadcast_texts(broadcast_id).await?;
+    let users = db.select_broadcast_recipients(broadcast_id).await?;
+    log::debug!("sending broadcast {broadcast_id} to {} users", users.len());
+
+    let admin_chat_id = ChatId(broadcast.admin_chat_id());
+    let admin_lang = db
+        .select_user(admin_chat_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|u| u.lang().to_string())
+        .unwrap_or(DEFAULT_USER_LANG.to_string());
+
+    let progress = db.load_broadcast_progress(broadcast_id).await?;
+    let progress_msg = bot
+        .send_message(
+            admin_chat_id,
+            progress_text(broadcast_id, progress, &admin_lang),
+        )
+        .await?;
+
+    let mut interval = time::interval(SEND_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_report = Instant::now();
+    for user in users {
+        interval.tick().await;
+        let status = if token.is_cancelled() {
+            None
+        } else {
+            send_to_user(bot, db, &user, &texts, token).await
+        };
+        let Some(status) = status else {
+            // delivery is saved for each user, so broadcast is resumed on start
+            log::info!("broadcast {broadcast_id} is interrupted by shutdown");
+            return Ok(());
+        };
+        db.save_broadcast_delivery(broadcast_id, user.user_id(), status)
+            .await
+            .log_error_msg("failed to save broadcast delivery");
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let progress = db.load_broadcast_progress(broadcast_id).await?;
+            bot.edit_message_text(
+                admin_chat_id,
+                progress_msg.id,
+                progress_text(broadcast_id, progress, &admin_lang),
+            )
+            .await
+            .log_error_msg("failed to report broadcast progress");
+        }
+    }
+
+    db.save_broadcast_status(
+        broadcast_id,
+        BroadcastStatus::Running,
+        BroadcastStatus::Done,
+    )
+    .await?;
+    let progress = db.load_broadcast_progress(broadcast_id).await?;
+    log::info!("broadcast {broadcast_id} finished: {progress:?}");
+    bot.edit_message_text(
+        admin_chat_id,
+        progress_msg.id,
+        tr!(
+            broadcast_finished,
+            &admin_lang,
+            &broadcast_id.to_string(),
+            &progress.sent.to_string(),
+            &progress.failed.to_string(),
+            &progress.blocked.to_string()
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Send broadcast to user, retrying while rate limited. Returns `None` if
+/// `token` is cancelled while waiting, then user is left undelivered
+async fn send_to_user<R: UserRepo>(
+    bot: &Bot,
+    db: &R,
+    user: &User,
+    texts: &[BroadcastText],
+    token: &CancellationToken,
+) -> Option<DeliveryStatus> {
+    let chat_id = ChatId(user.user_id());
+    let Some(text) = text_for_lang(texts, user.lang()) else {
+        log::error!("broadcast without default text");
+        return Some(DeliveryStatus::Failed);
+    };
+
+    let res = loop {
+        let res = bot
+            .send_message(chat_id, text)
+            .disable_notification(user.delivery_mode() == DeliveryMode::Silent)
+            .await;
+        match res {
+            Err(RequestError::RetryAfter(secs)) => {
+                log::warn!("broadcast is rate limited, waiting {secs}");
+                tokio::select! {
+                    _ = time::sleep(secs.duration()) => {}
+                    _ = token.cancelled() => return None,
+                }
+            }
+            res => break res,
+        }
+    };
+
+    let res = res.map_bot_blocked_error(chat_id);
+    record_notification(&res);
+    let status = match res {
+        Ok(()) => DeliveryStatus::Sent,
+        Err(UpdateError::BotBlocked(chat_id)) => {
+            handle_bot_blocked(db, chat_id, ChatUnavailableError::BotBlocked).await;
+            DeliveryStatus::Blocked
+        }
+        Err(UpdateError::UserDeactivated(chat_id)) => {
+            handle_bot_blocked(db, chat_id, ChatUnavailableError::UserDeactivated).await;
+            DeliveryStatus::Blocked
+        }
+        Err(UpdateError::RequestError(e)) => {
+            log::error!("failed to send broadcast to user {chat_id}: {e}");
+            DeliveryStatus::Failed
+        }
+    };
+    Some(status)
+}
+
+fn progress_text(broadcast_id: Id, progress: BroadcastProgress, lang: &str) -> String {
+    tr!(
+        broadcast_progress,
+        lang,
+        &broadcast_id.to_string(),
+        &progress.sent.to_string(),
+        &progress.failed.to_string(),
+        &progress.blocked.to_string(),
+        &progress.left.to_string()
+    )
+}
+
+/// Split text of /broadcast command into texts for languages. Text for
+/// language starts after line `[lang]`, text before first such line is
+/// default for all languages.
+///
+/// Returns `None` if there is no default text, or language is repeated
+pub fn parse_broadcast(text: &str) -> Option<Vec<BroadcastText>> {
+    let languages = Localize::languages();
+
+    let mut texts: Vec<(&str, Vec<&str>)> = vec![("", vec![])];
+    for line in text.lines() {
+        let lang = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|l| l.strip_suffix(']'))
+            .filter(|l| languages.contains(l));
+        match lang {
+            Some(lang) if texts.iter().any(|(l, _)| *l == lang) => return None,
+            Some(lang) => texts.push((lang, vec![])),
+            None => texts.last_mut().expect("default text").1.push(line),
+        }
+    }
+
+    let texts: Vec<_> = texts
+        .into_iter()
+        .map(|(lang, lines)| (lang, lines.join("\n").trim().to_string()))
+        .filter(|(_, text)| !text.is_empty())
+        .map(|(lang, text)| BroadcastText::new(lang, &text))
+        .collect();
+    texts
+        .first()
+        .is_some_and(|t| t.lang().is_none())
+        .then_some(texts)
+}
+
+/// Text in user's language, or default one
+fn text_for_lang<'a>(texts: &'a [BroadcastText], lang: &str) -> Option<&'a str> {
+    texts
+        .iter()
+        .find(|t| t.lang() == Some(lang))
+        .or_else(|| texts.iter().find(|t| t.lang().is_none()))
+        .map(|t| t.text())
+}
+
+/// Message with all texts of broadcast, shown to admin before confirmation
+pub(crate) fn broadcast_preview(texts: &[BroadcastText], recipients: usize, lang: &str) -> String {
+    let mut parts = vec![tr!(broadcast_preview, lang, &recipients.to_string())];
+    for t in texts {
+        let header = match t.lang() {
+            Some(l) => format!("[{l}]"),
+            None => tr!(broadcast_default_text, lang),
+        };
+        parts.push(format!("{header}\n{}", t.text()));
+    }
+    parts.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_broadcast() {
+        let table = [
+            ("hello", Some(vec![BroadcastText::default_text("hello")])),
+            (
+                "hello\nworld\n[ru]\nпривет\n\n",
+                Some(vec![
+                    BroadcastText::default_text("hello\nworld"),
+                    BroadcastText::new("ru", "привет"),
+                ]),
+            ),
+            // unknown language is part of text
+            (
+                "hello\n[xx]\nworld",
+                Some(vec![BroadcastText::default_text("hello\n[xx]\nworld")]),
+            ),
+            ("", None),
+            ("[ru]\nпривет", None),
+            ("hello\n[ru]\nпривет\n[ru]\nпривет", None),
+        ];
+        for (i, (text, expected)) in table.into_iter().enumerate() {
+            assert_eq!(parse_broadcast(text), expected, "test table[{i}]");
+        }
+    }
+
+    #[test]
+    fn test_text_for_lang() {
+        let texts = [
+            BroadcastText::default_text("hello"),
+            BroadcastText::new("ru", "привет"),
+        ];
+        assert_eq!(text_for_lang(&texts, "ru"), Some("привет"));
+        assert_eq!(text_for_lang(&texts, "en"), Some("hello"));
+        assert_eq!(text_for_lang(&[], "en"), None);
+    }
+}
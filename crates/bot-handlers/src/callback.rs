@@ -1,14 +1,25 @@
 use anyhow::Result;
 
 use crate::keyboards::LanguagesKeyboardToken;
-use crate::{IGNORE_TOKEN, NOTIFY_FLAG, NOTIFY_TOKEN, SET_LANG_FLAG};
+use crate::settings::Navigation;
+use crate::{
+    BROADCAST_CANCEL_TOKEN, BROADCAST_CONFIRM_TOKEN, BROADCAST_FLAG, DELETE_ME_CANCEL_TOKEN,
+    DELETE_ME_CONFIRM_TOKEN, DELETE_ME_FLAG, IGNORE_TOKEN, NOTIFY_FLAG, NOTIFY_TOKEN,
+    SETTINGS_BACK_TOKEN, SETTINGS_CLOSE_TOKEN, SETTINGS_FLAG, SETTINGS_OPEN_TOKEN,
+    SET_DELIVERY_FLAG, SET_LANG_FLAG, SET_NEW_SOURCES_FLAG, SET_TIMEZONE_FLAG, SET_VERBOSITY_FLAG,
+    SUBSCRIBE_FLAG, SUBSCRIBE_OFF_TOKEN, SUBSCRIBE_ON_TOKEN,
+};
 
-use db::models::ShouldNotify;
+use db::{
+    models::{DeliveryMode, NewSourcesMode, ShouldNotify, Verbosity},
+    types::Id,
+};
 
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub(crate) enum Callback {
     Notify {
+        source_id: Id,
         app_id: String,
         should_notify: ShouldNotify,
     },
@@ -16,6 +27,52 @@ pub(crate) enum Callback {
         lang: String,
         token: LanguagesKeyboardToken,
     },
+    /// Navigate inside /settings menu
+    Settings(Navigation),
+    SetVerbosity {
+        verbosity: Verbosity,
+    },
+    SetDelivery {
+        mode: DeliveryMode,
+    },
+    /// Shift timezone by `delta` minutes
+    ShiftTimezone {
+        delta: i32,
+    },
+    Subscribe {
+        source_id: Id,
+        subscribe: bool,
+    },
+    SetNewSources {
+        mode: NewSourcesMode,
+    },
+    /// Admin confirmed or cancelled broadcast
+    Broadcast {
+        broadcast_id: Id,
+        confirm: bool,
+    },
+    /// User confirmed or cancelled deleting their data
+    DeleteMe {
+        confirm: bool,
+    },
+}
+
+impl Callback {
+    /// Type of callback, same as flag in callback data
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Notify { .. } => NOTIFY_FLAG,
+            Self::SetLang { .. } => SET_LANG_FLAG,
+            Self::Settings(_) => SETTINGS_FLAG,
+            Self::SetVerbosity { .. } => SET_VERBOSITY_FLAG,
+            Self::SetDelivery { .. } => SET_DELIVERY_FLAG,
+            Self::ShiftTimezone { .. } => SET_TIMEZONE_FLAG,
+            Self::Subscribe { .. } => SUBSCRIBE_FLAG,
+            Self::SetNewSources { .. } => SET_NEW_SOURCES_FLAG,
+            Self::Broadcast { .. } => BROADCAST_FLAG,
+            Self::DeleteMe { .. } => DELETE_ME_FLAG,
+        }
+    }
 }
 
 impl TryFrom<&str> for Callback {
@@ -25,16 +82,16 @@ impl TryFrom<&str> for Callback {
         let data: Vec<_> = value.split(':').collect();
         let res = match data[0] {
             NOTIFY_FLAG => {
-                if data.len() < 3 {
+                if data.len() < 4 {
                     return Err(CallbackParseError::InvalidCallback);
                 }
 
-                let (app_id, should_notify) = if data.len() == 3 {
-                    (data[1].to_string(), data[2])
-                } else {
-                    // if data[1] contains ':'
-                    (data[1..data.len() - 1].join(":"), data[data.len() - 1])
+                let Ok(source_id) = data[1].parse() else {
+                    return Err(CallbackParseError::InvalidCallback);
                 };
+                // app_id may contain ':'
+                let (app_id, should_notify) =
+                    (data[2..data.len() - 1].join(":"), data[data.len() - 1]);
 
                 let should_notify = match should_notify {
                     NOTIFY_TOKEN => ShouldNotify::Notify,
@@ -44,6 +101,7 @@ impl TryFrom<&str> for Callback {
                     }
                 };
                 Callback::Notify {
+                    source_id,
                     app_id,
                     should_notify,
                 }
@@ -59,6 +117,109 @@ impl TryFrom<&str> for Callback {
                 };
                 Callback::SetLang { lang, token }
             }
+            SETTINGS_FLAG => {
+                let navigation = match (data.len(), data.get(1).copied()) {
+                    (3, Some(SETTINGS_OPEN_TOKEN)) => {
+                        let Ok(page) = data[2].try_into() else {
+                            return Err(CallbackParseError::InvalidToken);
+                        };
+                        Navigation::Open(page)
+                    }
+                    (2, Some(SETTINGS_BACK_TOKEN)) => Navigation::Back,
+                    (2, Some(SETTINGS_CLOSE_TOKEN)) => Navigation::Close,
+                    (2 | 3, _) => return Err(CallbackParseError::InvalidToken),
+                    _ => return Err(CallbackParseError::InvalidCallback),
+                };
+                Callback::Settings(navigation)
+            }
+            SET_VERBOSITY_FLAG => {
+                if data.len() != 2 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let Ok(verbosity) = data[1].try_into() else {
+                    return Err(CallbackParseError::InvalidToken);
+                };
+                Callback::SetVerbosity { verbosity }
+            }
+            SET_DELIVERY_FLAG => {
+                if data.len() != 2 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let Ok(mode) = data[1].try_into() else {
+                    return Err(CallbackParseError::InvalidToken);
+                };
+                Callback::SetDelivery { mode }
+            }
+            SET_TIMEZONE_FLAG => {
+                if data.len() != 2 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let Ok(delta) = data[1].parse() else {
+                    return Err(CallbackParseError::InvalidToken);
+                };
+                Callback::ShiftTimezone { delta }
+            }
+            SUBSCRIBE_FLAG => {
+                if data.len() != 3 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let Ok(source_id) = data[1].parse() else {
+                    return Err(CallbackParseError::InvalidCallback);
+                };
+                let subscribe = match data[2] {
+                    SUBSCRIBE_ON_TOKEN => true,
+                    SUBSCRIBE_OFF_TOKEN => false,
+                    _ => return Err(CallbackParseError::InvalidToken),
+                };
+                Callback::Subscribe {
+                    source_id,
+                    subscribe,
+                }
+            }
+            SET_NEW_SOURCES_FLAG => {
+                if data.len() != 2 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let Ok(mode) = data[1].try_into() else {
+                    return Err(CallbackParseError::InvalidToken);
+                };
+                Callback::SetNewSources { mode }
+            }
+            BROADCAST_FLAG => {
+                if data.len() != 3 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let Ok(broadcast_id) = data[1].parse() else {
+                    return Err(CallbackParseError::InvalidCallback);
+                };
+                let confirm = match data[2] {
+                    BROADCAST_CONFIRM_TOKEN => true,
+                    BROADCAST_CANCEL_TOKEN => false,
+                    _ => return Err(CallbackParseError::InvalidToken),
+                };
+                Callback::Broadcast {
+                    broadcast_id,
+                    confirm,
+                }
+            }
+            DELETE_ME_FLAG => {
+                if data.len() != 2 {
+                    return Err(CallbackParseError::InvalidCallback);
+                }
+
+                let confirm = match data[1] {
+                    DELETE_ME_CONFIRM_TOKEN => true,
+                    DELETE_ME_CANCEL_TOKEN => false,
+                    _ => return Err(CallbackParseError::InvalidToken),
+                };
+                Callback::DeleteMe { confirm }
+            }
             _ => return Err(CallbackParseError::UnknownCallbackType),
         };
         Ok(res)
@@ -83,8 +244,9 @@ pub(crate) enum CallbackParseError {
 
 #[cfg(test)]
 impl Callback {
-    fn notify(app_id: &str, should_notify: ShouldNotify) -> Self {
+    fn notify(source_id: Id, app_id: &str, should_notify: ShouldNotify) -> Self {
         Self::Notify {
+            source_id,
             app_id: app_id.to_string(),
             should_notify,
         }
@@ -101,22 +263,33 @@ impl Callback {
 mod tests {
     use super::*;
 
+    use crate::keyboards::SettingsPage;
+
     #[test]
     fn test_callback_from_str() {
         let app_id = "some-app";
         let strange_app_id = "some-app:name";
         let table = vec![
             (
-                format!("{NOTIFY_FLAG}:{app_id}:{NOTIFY_TOKEN}"),
-                Ok(Callback::notify(app_id, ShouldNotify::Notify)),
+                format!("{NOTIFY_FLAG}:1:{app_id}:{NOTIFY_TOKEN}"),
+                Ok(Callback::notify(1, app_id, ShouldNotify::Notify)),
+            ),
+            (
+                format!("{NOTIFY_FLAG}:2:{app_id}:{IGNORE_TOKEN}"),
+                Ok(Callback::notify(2, app_id, ShouldNotify::Ignore)),
+            ),
+            (
+                format!("{NOTIFY_FLAG}:1:{strange_app_id}:{IGNORE_TOKEN}"),
+                Ok(Callback::notify(1, strange_app_id, ShouldNotify::Ignore)),
             ),
+            // without source
             (
                 format!("{NOTIFY_FLAG}:{app_id}:{IGNORE_TOKEN}"),
-                Ok(Callback::notify(app_id, ShouldNotify::Ignore)),
+                Err(CallbackParseError::InvalidCallback),
             ),
             (
                 format!("{NOTIFY_FLAG}:{strange_app_id}:{IGNORE_TOKEN}"),
-                Ok(Callback::notify(strange_app_id, ShouldNotify::Ignore)),
+                Err(CallbackParseError::InvalidCallback),
             ),
             (
                 format!("{SET_LANG_FLAG}:start:en"),
@@ -131,15 +304,87 @@ mod tests {
                 Err(CallbackParseError::InvalidToken),
             ),
             (
-                format!("{NOTIFY_FLAG}:{app_id}:asdf"),
+                format!("{SETTINGS_FLAG}:open:verbosity"),
+                Ok(Callback::Settings(Navigation::Open(
+                    SettingsPage::Verbosity,
+                ))),
+            ),
+            (
+                format!("{SETTINGS_FLAG}:back"),
+                Ok(Callback::Settings(Navigation::Back)),
+            ),
+            (
+                format!("{SETTINGS_FLAG}:open:unknown"),
+                Err(CallbackParseError::InvalidToken),
+            ),
+            (
+                format!("{SETTINGS_FLAG}:back:main:more"),
+                Err(CallbackParseError::InvalidCallback),
+            ),
+            (
+                format!("{SET_TIMEZONE_FLAG}:-30"),
+                Ok(Callback::ShiftTimezone { delta: -30 }),
+            ),
+            (
+                format!("{SET_TIMEZONE_FLAG}:abc"),
+                Err(CallbackParseError::InvalidToken),
+            ),
+            (
+                format!("{BROADCAST_FLAG}:3:confirm"),
+                Ok(Callback::Broadcast {
+                    broadcast_id: 3,
+                    confirm: true,
+                }),
+            ),
+            (
+                format!("{BROADCAST_FLAG}:3:send"),
+                Err(CallbackParseError::InvalidToken),
+            ),
+            (
+                format!("{DELETE_ME_FLAG}:confirm"),
+                Ok(Callback::DeleteMe { confirm: true }),
+            ),
+            (
+                format!("{DELETE_ME_FLAG}:delete"),
+                Err(CallbackParseError::InvalidToken),
+            ),
+            (
+                format!("{SUBSCRIBE_FLAG}:2:off"),
+                Ok(Callback::Subscribe {
+                    source_id: 2,
+                    subscribe: false,
+                }),
+            ),
+            (
+                format!("{SUBSCRIBE_FLAG}:off"),
+                Err(CallbackParseError::InvalidCallback),
+            ),
+            (
+                format!("{SET_NEW_SOURCES_FLAG}:subscribe"),
+                Ok(Callback::SetNewSources {
+                    mode: NewSourcesMode::Subscribe,
+                }),
+            ),
+            (
+                format!("{SET_VERBOSITY_FLAG}:detailed"),
+                Ok(Callback::SetVerbosity {
+                    verbosity: Verbosity::Detailed,
+                }),
+            ),
+            (
+                format!("{SET_VERBOSITY_FLAG}:detailed:more"),
+                Err(CallbackParseError::InvalidCallback),
+            ),
+            (
+                format!("{NOTIFY_FLAG}:1:{app_id}:asdf"),
                 Err(CallbackParseError::InvalidToken),
             ),
             (
-                format!("{NOTIFY_FLAG}:{app_id}"),
+                format!("{NOTIFY_FLAG}:1:{app_id}"),
                 Err(CallbackParseError::InvalidCallback),
             ),
             (
-                format!("{NOTIFY_FLAG}:{strange_app_id}"),
+                format!("{NOTIFY_FLAG}:1:{strange_app_id}"),
                 Err(CallbackParseError::InvalidToken),
             ),
         ];
@@ -1,3 +1,4 @@
+use common::AdminRole;
 use i18n::tr_literal;
 use teloxide::{
     macros::BotCommands as DeriveBotCommands, types::BotCommand, utils::command::BotCommands,
@@ -20,6 +21,10 @@ pub enum Command {
     About,
     #[command(description = "$help-command")]
     Help,
+    #[command(description = "$mydata-command")]
+    MyData,
+    #[command(description = "$deleteme-command")]
+    DeleteMe,
 }
 
 impl Command {
@@ -29,24 +34,68 @@ impl Command {
     /// Check if command allowed in public chats
     pub(crate) fn allowed_in_public(self) -> bool {
         match self {
-            Self::Start | Self::Subscribe | Self::Unsubscribe => false,
+            Self::Start | Self::Subscribe | Self::Unsubscribe | Self::MyData | Self::DeleteMe => {
+                false
+            }
             Self::Changelog | Self::Settings | Self::About | Self::Help => true,
         }
     }
 }
 
-#[derive(BotCommands, Clone, Copy)]
+#[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
 pub enum AdminCommand {
     #[command(description = "$stats-command")]
     Stats,
+    /// Text for all users, see [`crate::broadcast::parse_broadcast`]
+    #[command(description = "$broadcast-command")]
+    Broadcast(String),
+    /// User id or @username
+    #[command(description = "$user-command")]
+    User(String),
+    #[command(description = "$users-command")]
+    Users,
+    #[command(description = "$ban-command")]
+    Ban(String),
+    #[command(description = "$unban-command")]
+    Unban(String),
+    #[command(description = "$sources-command")]
+    Sources,
+    /// Source name, or empty for all sources
+    #[command(description = "$fetch-command")]
+    Fetch(String),
+    /// Source name, or empty for all sources
+    #[command(description = "$preview-command")]
+    Preview(String),
+    #[command(description = "$backup-command")]
+    Backup,
     #[command(hide)]
     Help,
 }
 
 impl AdminCommand {
-    pub fn bot_commands_translated(lang: &str) -> impl IntoIterator<Item = BotCommand> {
-        translate_bot_commands(Self::bot_commands(), lang)
+    /// Commands, available for admin with `role`
+    pub fn bot_commands_translated(
+        role: AdminRole,
+        lang: &str,
+    ) -> impl IntoIterator<Item = BotCommand> {
+        translate_bot_commands(Self::bot_commands_for_role(role), lang)
+    }
+    fn bot_commands_for_role(role: AdminRole) -> Vec<BotCommand> {
+        Self::bot_commands()
+            .into_iter()
+            .filter(|c| Self::parse(&c.command, "").is_ok_and(|cmd| role >= cmd.required_role()))
+            .collect()
+    }
+    /// Minimal role of admin, who can run command
+    pub fn required_role(&self) -> AdminRole {
+        match self {
+            Self::Stats | Self::User(_) | Self::Users | Self::Sources | Self::Help => {
+                AdminRole::Viewer
+            }
+            Self::Fetch(_) | Self::Preview(_) => AdminRole::Operator,
+            Self::Broadcast(_) | Self::Ban(_) | Self::Unban(_) | Self::Backup => AdminRole::Owner,
+        }
     }
 }
 
@@ -65,3 +114,46 @@ fn translate_bot_commands(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_commands_for_role() {
+        let table = [
+            (
+                AdminRole::Viewer,
+                vec!["/stats", "/user", "/users", "/sources"],
+            ),
+            (
+                AdminRole::Operator,
+                vec![
+                    "/stats", "/user", "/users", "/sources", "/fetch", "/preview",
+                ],
+            ),
+            (
+                AdminRole::Owner,
+                vec![
+                    "/stats",
+                    "/broadcast",
+                    "/user",
+                    "/users",
+                    "/ban",
+                    "/unban",
+                    "/sources",
+                    "/fetch",
+                    "/preview",
+                    "/backup",
+                ],
+            ),
+        ];
+        for (i, (role, expected)) in table.into_iter().enumerate() {
+            let commands: Vec<_> = AdminCommand::bot_commands_for_role(role)
+                .into_iter()
+                .map(|c| c.command)
+                .collect();
+            assert_eq!(commands, expected, "test table[{i}]");
+        }
+    }
+}
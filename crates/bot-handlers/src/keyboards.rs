@@ -3,13 +3,29 @@ use std::fmt::Display;
 use reqwest::Url;
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ReplyMarkup};
 
-use crate::{tr, IGNORE_TOKEN, NOTIFY_FLAG, NOTIFY_TOKEN, SET_LANG_FLAG};
+use serde::{Deserialize, Serialize};
+
+use db::{
+    models::{DeliveryMode, NewSourcesMode, SourceSubscription, Verbosity},
+    types::Id,
+};
+
+use crate::{
+    tr, BROADCAST_CANCEL_TOKEN, BROADCAST_CONFIRM_TOKEN, BROADCAST_FLAG, DELETE_ME_CANCEL_TOKEN,
+    DELETE_ME_CONFIRM_TOKEN, DELETE_ME_FLAG, IGNORE_TOKEN, NOTIFY_FLAG, NOTIFY_TOKEN,
+    SETTINGS_BACK_TOKEN, SETTINGS_CLOSE_TOKEN, SETTINGS_FLAG, SETTINGS_OPEN_TOKEN,
+    SET_DELIVERY_FLAG, SET_LANG_FLAG, SET_NEW_SOURCES_FLAG, SET_TIMEZONE_FLAG, SET_VERBOSITY_FLAG,
+    SUBSCRIBE_FLAG, SUBSCRIBE_OFF_TOKEN, SUBSCRIBE_ON_TOKEN,
+};
 
 const BELL_MSG: &str = "🔔";
 const NO_BELL_MSG: &str = "🔕";
+const SELECTED_MSG: &str = "✓";
 
 #[derive(Debug, Default)]
 pub(crate) struct KeyboardBuilder {
+    /// Finished rows, added before `keys`
+    rows: Vec<Vec<InlineKeyboardButton>>,
     keys: Vec<InlineKeyboardButton>,
     columns: usize,
 }
@@ -17,10 +33,18 @@ pub(crate) struct KeyboardBuilder {
 impl KeyboardBuilder {
     fn with_layout(rows_capacity: usize, columns: usize) -> Self {
         Self {
+            rows: vec![],
             keys: Vec::with_capacity(rows_capacity * columns),
             columns,
         }
     }
+    /// Finish current keys, next keys will start from new row
+    fn new_row(mut self) -> Self {
+        let keys = std::mem::take(&mut self.keys);
+        self.rows
+            .extend(keys.chunks(self.columns).map(|row| row.to_owned()));
+        self
+    }
     fn callback<T, D>(mut self, text: T, data: D) -> Self
     where
         T: Into<String>,
@@ -46,7 +70,7 @@ impl From<KeyboardBuilder> for ReplyMarkup {
 
 impl From<KeyboardBuilder> for InlineKeyboardMarkup {
     fn from(value: KeyboardBuilder) -> Self {
-        Self::new(value.keys.chunks(value.columns).map(|row| row.to_owned()))
+        Self::new(value.new_row().rows)
     }
 }
 
@@ -54,6 +78,7 @@ pub(crate) struct Keyboards;
 
 impl Keyboards {
     pub(crate) fn update(
+        source_id: Id,
         app_id: &str,
         url: Option<Url>,
         kind: NewAppKeyboardKind,
@@ -63,16 +88,16 @@ impl Keyboards {
             NewAppKeyboardKind::Both => KeyboardBuilder::with_layout(2, 2)
                 .callback(
                     tr!(notify_button, lang),
-                    notify_payload(app_id, NOTIFY_TOKEN),
+                    notify_payload(source_id, app_id, NOTIFY_TOKEN),
                 )
                 .callback(
                     tr!(ignore_button, lang),
-                    notify_payload(app_id, IGNORE_TOKEN),
+                    notify_payload(source_id, app_id, IGNORE_TOKEN),
                 ),
             NewAppKeyboardKind::NotifyEnabled => KeyboardBuilder::with_layout(1, 2)
-                .callback(BELL_MSG, notify_payload(app_id, IGNORE_TOKEN)),
+                .callback(BELL_MSG, notify_payload(source_id, app_id, IGNORE_TOKEN)),
             NewAppKeyboardKind::NotifyDisabled => KeyboardBuilder::with_layout(1, 2)
-                .callback(NO_BELL_MSG, notify_payload(app_id, NOTIFY_TOKEN)),
+                .callback(NO_BELL_MSG, notify_payload(source_id, app_id, NOTIFY_TOKEN)),
         };
 
         if let Some(url) = url {
@@ -81,7 +106,9 @@ impl Keyboards {
             keyboard
         }
     }
-    pub(crate) fn languages(token: LanguagesKeyboardToken) -> KeyboardBuilder {
+    /// `lang` is used for "back" button, which is shown only when keyboard
+    /// is opened from settings
+    pub(crate) fn languages(token: LanguagesKeyboardToken, lang: &str) -> KeyboardBuilder {
         const LANGS_IN_ROW: usize = 3;
         let langs: Vec<&'static str> = i18n::Localize::languages();
         let mut keyboard =
@@ -90,11 +117,145 @@ impl Keyboards {
             keyboard = keyboard.callback(tr!(lang_name, lang), lang_payload(lang, token));
         }
 
-        keyboard
+        match token {
+            LanguagesKeyboardToken::Start => keyboard,
+            LanguagesKeyboardToken::Settings => back_button(keyboard, lang),
+        }
+    }
+    /// Main page of /settings
+    pub(crate) fn settings(lang: &str) -> KeyboardBuilder {
+        let pages = [
+            (SettingsPage::Language, tr!(settings_language_button, lang)),
+            (
+                SettingsPage::Verbosity,
+                tr!(settings_verbosity_button, lang),
+            ),
+            (SettingsPage::Delivery, tr!(settings_delivery_button, lang)),
+            (SettingsPage::Timezone, tr!(settings_timezone_button, lang)),
+            (SettingsPage::Sources, tr!(settings_sources_button, lang)),
+        ];
+        let mut keyboard = KeyboardBuilder::with_layout(pages.len() + 1, 1);
+        for (page, text) in pages {
+            keyboard = keyboard.callback(text, settings_open_payload(page));
+        }
+        keyboard.callback(tr!(close_button, lang), settings_close_payload())
+    }
+    pub(crate) fn verbosity(current: Verbosity, lang: &str) -> KeyboardBuilder {
+        let mut keyboard = KeyboardBuilder::with_layout(Verbosity::ALL.len() + 1, 1);
+        for v in Verbosity::ALL {
+            let name = verbosity_name(v, lang);
+            let text = if v == current {
+                format!("{SELECTED_MSG} {name}")
+            } else {
+                name
+            };
+            keyboard = keyboard.callback(text, verbosity_payload(v));
+        }
+        back_button(keyboard, lang)
+    }
+    pub(crate) fn delivery(current: DeliveryMode, lang: &str) -> KeyboardBuilder {
+        let mut keyboard = KeyboardBuilder::with_layout(DeliveryMode::ALL.len() + 1, 1);
+        for m in DeliveryMode::ALL {
+            let name = match m {
+                DeliveryMode::Normal => tr!(delivery_normal, lang),
+                DeliveryMode::Silent => tr!(delivery_silent, lang),
+            };
+            let text = if m == current {
+                format!("{SELECTED_MSG} {name}")
+            } else {
+                name
+            };
+            keyboard = keyboard.callback(text, delivery_payload(m));
+        }
+        back_button(keyboard, lang)
+    }
+    /// Buttons to shift timezone
+    pub(crate) fn timezone(lang: &str) -> KeyboardBuilder {
+        let mut keyboard = KeyboardBuilder::with_layout(2, 4);
+        for (text, delta) in [("-1h", -60), ("-30m", -30), ("+30m", 30), ("+1h", 60)] {
+            keyboard = keyboard.callback(text, timezone_payload(delta));
+        }
+        back_button(keyboard, lang)
+    }
+    /// Sources with subscription toggles
+    pub(crate) fn sources(
+        subscriptions: &[SourceSubscription],
+        new_sources: NewSourcesMode,
+        lang: &str,
+    ) -> KeyboardBuilder {
+        let mut keyboard = KeyboardBuilder::with_layout(subscriptions.len() + 2, 1);
+        for s in subscriptions {
+            let source = s.source();
+            let (icon, subscribe) = if s.subscribed() {
+                (BELL_MSG, false)
+            } else {
+                (NO_BELL_MSG, true)
+            };
+            keyboard = keyboard.callback(
+                format!("{icon} {}", source.display_name()),
+                subscribe_payload(source.source_id(), subscribe),
+            );
+        }
+        let keyboard = match new_sources {
+            NewSourcesMode::Ask => keyboard.callback(
+                tr!(new_sources_ask_button, lang),
+                new_sources_payload(NewSourcesMode::Subscribe),
+            ),
+            NewSourcesMode::Subscribe => keyboard.callback(
+                tr!(new_sources_subscribe_button, lang),
+                new_sources_payload(NewSourcesMode::Ask),
+            ),
+        };
+        back_button(keyboard, lang)
+    }
+    /// Confirm sending broadcast
+    pub(crate) fn broadcast(broadcast_id: Id, lang: &str) -> KeyboardBuilder {
+        KeyboardBuilder::with_layout(1, 2)
+            .callback(
+                tr!(broadcast_confirm_button, lang),
+                format!("{BROADCAST_FLAG}:{broadcast_id}:{BROADCAST_CONFIRM_TOKEN}"),
+            )
+            .callback(
+                tr!(broadcast_cancel_button, lang),
+                format!("{BROADCAST_FLAG}:{broadcast_id}:{BROADCAST_CANCEL_TOKEN}"),
+            )
+    }
+    /// Confirm deleting all data of user
+    pub(crate) fn delete_me(lang: &str) -> KeyboardBuilder {
+        KeyboardBuilder::with_layout(1, 2)
+            .callback(
+                tr!(deleteme_confirm_button, lang),
+                format!("{DELETE_ME_FLAG}:{DELETE_ME_CONFIRM_TOKEN}"),
+            )
+            .callback(
+                tr!(deleteme_cancel_button, lang),
+                format!("{DELETE_ME_FLAG}:{DELETE_ME_CANCEL_TOKEN}"),
+            )
+    }
+    /// Suggest subscribing to new source
+    pub(crate) fn new_source(source_id: Id, lang: &str) -> KeyboardBuilder {
+        KeyboardBuilder::with_layout(1, 1).callback(
+            tr!(subscribe_button, lang),
+            subscribe_payload(source_id, true),
+        )
     }
 }
 
-#[derive(Debug)]
+fn back_button(keyboard: KeyboardBuilder, lang: &str) -> KeyboardBuilder {
+    keyboard
+        .new_row()
+        .callback(tr!(back_button, lang), settings_back_payload())
+}
+
+pub(crate) fn verbosity_name(verbosity: Verbosity, lang: &str) -> String {
+    match verbosity {
+        Verbosity::Minimal => tr!(verbosity_minimal, lang),
+        Verbosity::Normal => tr!(verbosity_normal, lang),
+        Verbosity::Detailed => tr!(verbosity_detailed, lang),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub(crate) enum NewAppKeyboardKind {
     /// Show both buttons
     Both,
@@ -135,14 +296,94 @@ impl TryFrom<&str> for LanguagesKeyboardToken {
     }
 }
 
-fn notify_payload(app_id: &str, token: &str) -> String {
-    format!("{NOTIFY_FLAG}:{app_id}:{token}")
+/// Pages of /settings menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SettingsPage {
+    Main,
+    Language,
+    Verbosity,
+    Delivery,
+    Timezone,
+    Sources,
+}
+
+impl Display for SettingsPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Main => "main",
+            Self::Language => "lang",
+            Self::Verbosity => "verbosity",
+            Self::Delivery => "delivery",
+            Self::Timezone => "tz",
+            Self::Sources => "sources",
+        };
+        s.fmt(f)
+    }
+}
+
+impl TryFrom<&str> for SettingsPage {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let s = match value {
+            "main" => Some(Self::Main),
+            "lang" => Some(Self::Language),
+            "verbosity" => Some(Self::Verbosity),
+            "delivery" => Some(Self::Delivery),
+            "tz" => Some(Self::Timezone),
+            "sources" => Some(Self::Sources),
+            _ => None,
+        };
+        s.ok_or(())
+    }
+}
+
+fn notify_payload(source_id: Id, app_id: &str, token: &str) -> String {
+    format!("{NOTIFY_FLAG}:{source_id}:{app_id}:{token}")
 }
 
 fn lang_payload(lang: &str, token: LanguagesKeyboardToken) -> String {
     format!("{SET_LANG_FLAG}:{token}:{lang}")
 }
 
+fn settings_open_payload(page: SettingsPage) -> String {
+    format!("{SETTINGS_FLAG}:{SETTINGS_OPEN_TOKEN}:{page}")
+}
+
+fn settings_back_payload() -> String {
+    format!("{SETTINGS_FLAG}:{SETTINGS_BACK_TOKEN}")
+}
+
+fn settings_close_payload() -> String {
+    format!("{SETTINGS_FLAG}:{SETTINGS_CLOSE_TOKEN}")
+}
+
+fn verbosity_payload(verbosity: Verbosity) -> String {
+    format!("{SET_VERBOSITY_FLAG}:{}", verbosity.as_str())
+}
+
+fn delivery_payload(mode: DeliveryMode) -> String {
+    format!("{SET_DELIVERY_FLAG}:{}", mode.as_str())
+}
+
+/// `delta` is minutes to add to current timezone
+fn timezone_payload(delta: i32) -> String {
+    format!("{SET_TIMEZONE_FLAG}:{delta}")
+}
+
+fn subscribe_payload(source_id: Id, subscribe: bool) -> String {
+    let token = if subscribe {
+        SUBSCRIBE_ON_TOKEN
+    } else {
+        SUBSCRIBE_OFF_TOKEN
+    };
+    format!("{SUBSCRIBE_FLAG}:{source_id}:{token}")
+}
+
+fn new_sources_payload(mode: NewSourcesMode) -> String {
+    format!("{SET_NEW_SOURCES_FLAG}:{}", mode.as_str())
+}
+
 #[cfg(test)]
 mod tests {
     use teloxide::types::{
@@ -165,26 +406,32 @@ mod tests {
         let update_btn = Btn::url(SEE_UPDATE_MSG, url.clone());
         let table = vec![
             (
-                Keyboards::update(APP_ID, Some(url.clone()), Kind::Both, USER_LANG),
+                Keyboards::update(1, APP_ID, Some(url.clone()), Kind::Both, USER_LANG),
                 vec![
                     vec![
-                        Btn::callback(NOTIFY_MSG, "notify:test:notify"),
-                        Btn::callback(IGNORE_MSG, "notify:test:ignore"),
+                        Btn::callback(NOTIFY_MSG, "notify:1:test:notify"),
+                        Btn::callback(IGNORE_MSG, "notify:1:test:ignore"),
                     ],
                     vec![update_btn.clone()],
                 ],
             ),
             (
-                Keyboards::update(APP_ID, Some(url.clone()), Kind::NotifyEnabled, USER_LANG),
+                Keyboards::update(1, APP_ID, Some(url.clone()), Kind::NotifyEnabled, USER_LANG),
                 vec![vec![
-                    Btn::callback(BELL_MSG, "notify:test:ignore"),
+                    Btn::callback(BELL_MSG, "notify:1:test:ignore"),
                     update_btn.clone(),
                 ]],
             ),
             (
-                Keyboards::update(APP_ID, Some(url.clone()), Kind::NotifyDisabled, USER_LANG),
+                Keyboards::update(
+                    1,
+                    APP_ID,
+                    Some(url.clone()),
+                    Kind::NotifyDisabled,
+                    USER_LANG,
+                ),
                 vec![vec![
-                    Btn::callback(NO_BELL_MSG, "notify:test:notify"),
+                    Btn::callback(NO_BELL_MSG, "notify:1:test:notify"),
                     update_btn.clone(),
                 ]],
             ),
@@ -194,4 +441,45 @@ mod tests {
             assert_eq!(res, Reply::InlineKeyboard(Markup::new(expected)));
         }
     }
+
+    #[test]
+    fn test_settings_keyboards() {
+        let back_btn = Btn::callback("« Back", "settings:back");
+        // order of languages is not stable
+        let langs_btns = i18n::Localize::languages()
+            .into_iter()
+            .map(|l| Btn::callback(tr!(lang_name, l), format!("lang:settings:{l}")))
+            .collect();
+        let table = vec![
+            (
+                Keyboards::languages(LanguagesKeyboardToken::Settings, USER_LANG),
+                vec![langs_btns, vec![back_btn.clone()]],
+            ),
+            (
+                Keyboards::verbosity(Verbosity::Normal, USER_LANG),
+                vec![
+                    vec![Btn::callback("Minimal", "verbosity:minimal")],
+                    vec![Btn::callback("✓ Normal", "verbosity:normal")],
+                    vec![Btn::callback("Detailed", "verbosity:detailed")],
+                    vec![back_btn.clone()],
+                ],
+            ),
+            (
+                Keyboards::timezone(USER_LANG),
+                vec![
+                    vec![
+                        Btn::callback("-1h", "tz:-60"),
+                        Btn::callback("-30m", "tz:-30"),
+                        Btn::callback("+30m", "tz:30"),
+                        Btn::callback("+1h", "tz:60"),
+                    ],
+                    vec![back_btn.clone()],
+                ],
+            ),
+        ];
+        for (res, expected) in table {
+            let res: ReplyMarkup = res.into();
+            assert_eq!(res, Reply::InlineKeyboard(Markup::new(expected)));
+        }
+    }
 }
@@ -1,9 +1,13 @@
+mod backup;
 mod bot_admin_messages;
 mod bot_callback;
 mod bot_messages;
+mod broadcast;
 mod callback;
 mod commands;
 mod keyboards;
+mod settings;
+mod source_health;
 mod updates_notify;
 mod user;
 mod utils;
@@ -11,16 +15,45 @@ mod utils;
 // flags is at the start of message: {flag}:{payload}
 const NOTIFY_FLAG: &str = "notify";
 const SET_LANG_FLAG: &str = "lang";
+const SETTINGS_FLAG: &str = "settings";
+const SET_VERBOSITY_FLAG: &str = "verbosity";
+const SET_DELIVERY_FLAG: &str = "delivery";
+const SET_TIMEZONE_FLAG: &str = "tz";
+const SUBSCRIBE_FLAG: &str = "sub";
+const SET_NEW_SOURCES_FLAG: &str = "newsrc";
+const BROADCAST_FLAG: &str = "broadcast";
+const DELETE_ME_FLAG: &str = "deleteme";
+
+// payload tokens: {settings-flag}:{token}[:{page}]
+const SETTINGS_OPEN_TOKEN: &str = "open";
+const SETTINGS_BACK_TOKEN: &str = "back";
+const SETTINGS_CLOSE_TOKEN: &str = "close";
+
+// payload tokens: {subscribe-flag}:{source-id}:{token}
+const SUBSCRIBE_ON_TOKEN: &str = "on";
+const SUBSCRIBE_OFF_TOKEN: &str = "off";
+
+// payload tokens: {broadcast-flag}:{broadcast-id}:{token}
+const BROADCAST_CONFIRM_TOKEN: &str = "confirm";
+const BROADCAST_CANCEL_TOKEN: &str = "cancel";
+
+// payload tokens: {delete-me-flag}:{token}
+const DELETE_ME_CONFIRM_TOKEN: &str = "confirm";
+const DELETE_ME_CANCEL_TOKEN: &str = "cancel";
 
 // payload tokens: {notify-flag}:{app-id}:{token}
 const IGNORE_TOKEN: &str = "ignore";
 const NOTIFY_TOKEN: &str = "notify";
 
-pub use bot_admin_messages::admin_command_handler;
+pub use backup::start_backup_job;
+pub use bot_admin_messages::{admin_command_handler, admin_forbidden_handler};
 pub use bot_callback::callback_handler;
 pub use bot_messages::{command_handler, message_handler};
+pub use broadcast::{parse_broadcast, run_broadcast, start_broadcast_job, BroadcastQueue};
 pub use commands::{AdminCommand, Command};
+pub use settings::{SettingsState, SettingsStorage};
+pub use source_health::start_source_health_job;
 pub use updates_notify::start_updates_notify_job;
-pub use user::run_collect_user_names_job;
+pub use user::{is_not_banned, run_collect_user_names_job, start_retention_job};
 
 pub(crate) use i18n::{tr, DEFAULT_USER_LANG};
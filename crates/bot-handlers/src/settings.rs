@@ -0,0 +1,325 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    dispatching::dialogue::{
+        serializer::{Json, Serializer},
+        Dialogue, Storage,
+    },
+    types::ChatId,
+};
+
+use common::{DateTime, LogError};
+use db::{
+    models::{DeliveryMode, NewSourcesMode, SourceSubscription, User, Verbosity},
+    types, DialogueRepo, SubscriptionRepo,
+};
+
+use crate::{
+    keyboards::{KeyboardBuilder, Keyboards, LanguagesKeyboardToken, SettingsPage},
+    tr,
+};
+
+/// Storage for state of /settings menu, survives restarts. State is kept in
+/// bot's database, so it works with any backend
+#[derive(Debug)]
+pub struct SettingsStorage<R> {
+    db: R,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsStorageError {
+    #[error(transparent)]
+    Db(#[from] db::Error),
+    #[error("failed to (de)serialize settings state: {0}")]
+    Serde(<Json as Serializer<SettingsState>>::Error),
+    #[error("dialogue not found")]
+    DialogueNotFound,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = Result<T, SettingsStorageError>> + Send>>;
+
+impl<R> SettingsStorage<R> {
+    pub fn new(db: R) -> Arc<Self> {
+        Arc::new(Self { db })
+    }
+}
+
+impl<R> Storage<SettingsState> for SettingsStorage<R>
+where
+    R: DialogueRepo + Send + Sync + 'static,
+{
+    type Error = SettingsStorageError;
+
+    fn remove_dialogue(self: Arc<Self>, ChatId(chat_id): ChatId) -> BoxFuture<()> {
+        Box::pin(async move {
+            if !self.db.remove_dialogue(chat_id).await? {
+                return Err(SettingsStorageError::DialogueNotFound);
+            }
+            Ok(())
+        })
+    }
+    fn update_dialogue(
+        self: Arc<Self>,
+        ChatId(chat_id): ChatId,
+        dialogue: SettingsState,
+    ) -> BoxFuture<()> {
+        Box::pin(async move {
+            let bytes = Json
+                .serialize(&dialogue)
+                .map_err(SettingsStorageError::Serde)?;
+            Ok(self.db.save_dialogue(chat_id, &bytes).await?)
+        })
+    }
+    fn get_dialogue(self: Arc<Self>, ChatId(chat_id): ChatId) -> BoxFuture<Option<SettingsState>> {
+        Box::pin(async move {
+            self.db
+                .select_dialogue(chat_id)
+                .await?
+                .map(|bytes| {
+                    Json.deserialize(&bytes)
+                        .map_err(SettingsStorageError::Serde)
+                })
+                .transpose()
+        })
+    }
+}
+
+pub(crate) type SettingsDialogue<R> = Dialogue<SettingsState, SettingsStorage<R>>;
+
+/// Minimal and maximal timezones
+pub(crate) const MIN_UTC_OFFSET: i32 = -12 * 60;
+pub(crate) const MAX_UTC_OFFSET: i32 = 14 * 60;
+
+/// State of /settings menu
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SettingsState {
+    #[default]
+    Closed,
+    Open {
+        /// Message with menu
+        message_id: i32,
+        /// Opened pages, last one is shown
+        path: Vec<SettingsPage>,
+    },
+}
+
+impl SettingsState {
+    /// Menu opened on main page
+    pub(crate) fn opened(message_id: i32) -> Self {
+        Self::Open {
+            message_id,
+            path: vec![SettingsPage::Main],
+        }
+    }
+    /// Apply navigation to menu in message `message_id`. If there is a state
+    /// for another message (e.g. /settings was sent again), that menu is
+    /// treated as opened on main page
+    pub(crate) fn navigate(self, navigation: Navigation, message_id: i32) -> Self {
+        let mut path = match self {
+            Self::Open {
+                message_id: id,
+                path,
+            } if id == message_id && !path.is_empty() => path,
+            _ => vec![SettingsPage::Main],
+        };
+        match navigation {
+            Navigation::Open(SettingsPage::Main) => path = vec![SettingsPage::Main],
+            Navigation::Open(page) => {
+                if path.last() != Some(&page) {
+                    path.push(page);
+                }
+            }
+            Navigation::Back => {
+                if path.len() > 1 {
+                    path.pop();
+                }
+            }
+            Navigation::Close => return Self::Closed,
+        }
+        Self::Open { message_id, path }
+    }
+    /// Currently shown page, `None` if menu is closed
+    pub(crate) fn page(&self) -> Option<SettingsPage> {
+        match self {
+            Self::Closed => None,
+            Self::Open { path, .. } => path.last().copied(),
+        }
+    }
+}
+
+/// Navigation inside /settings menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Navigation {
+    /// Go forward to page
+    Open(SettingsPage),
+    /// Return to previous page
+    Back,
+    Close,
+}
+
+/// Current values of user's settings
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SettingsValues {
+    pub(crate) verbosity: Verbosity,
+    pub(crate) delivery_mode: DeliveryMode,
+    /// Minutes from UTC
+    pub(crate) utc_offset: i32,
+    pub(crate) subscriptions: Vec<SourceSubscription>,
+    pub(crate) new_sources: NewSourcesMode,
+}
+
+impl SettingsValues {
+    pub(crate) async fn load(
+        db: &impl SubscriptionRepo,
+        user: Option<&User>,
+        user_id: impl Into<types::UserId> + Send,
+    ) -> Self {
+        let subscriptions = db
+            .select_user_subscriptions(user_id)
+            .await
+            .log_error_msg("failed to select user subscriptions")
+            .as_ref()
+            .map(|s| s.to_owned())
+            .unwrap_or_default();
+        match user {
+            Some(u) => Self {
+                verbosity: u.verbosity(),
+                delivery_mode: u.delivery_mode(),
+                utc_offset: u.utc_offset(),
+                subscriptions,
+                new_sources: u.new_sources(),
+            },
+            None => Self {
+                subscriptions,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Text and keyboard for page of /settings menu
+pub(crate) fn settings_page(
+    page: SettingsPage,
+    values: &SettingsValues,
+    lang: &str,
+) -> (String, KeyboardBuilder) {
+    match page {
+        SettingsPage::Main => (tr!(settings_header, lang), Keyboards::settings(lang)),
+        SettingsPage::Language => (
+            tr!(choose_language, lang),
+            Keyboards::languages(LanguagesKeyboardToken::Settings, lang),
+        ),
+        SettingsPage::Verbosity => (
+            tr!(settings_verbosity_header, lang),
+            Keyboards::verbosity(values.verbosity, lang),
+        ),
+        SettingsPage::Delivery => (
+            tr!(settings_delivery_header, lang),
+            Keyboards::delivery(values.delivery_mode, lang),
+        ),
+        SettingsPage::Timezone => (
+            tr!(
+                settings_timezone_header,
+                lang,
+                &DateTime::format_utc_offset(values.utc_offset)
+            ),
+            Keyboards::timezone(lang),
+        ),
+        SettingsPage::Sources => {
+            let sources = values
+                .subscriptions
+                .iter()
+                .map(|s| {
+                    let source = s.source();
+                    format!("• {} — {}", source.display_name(), source.description())
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            (
+                [tr!(settings_sources_header, lang), sources].join("\n\n"),
+                Keyboards::sources(&values.subscriptions, values.new_sources, lang),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use SettingsPage as Page;
+
+    #[test]
+    fn test_settings_navigation() {
+        const MSG: i32 = 1;
+        let open = |path: Vec<Page>| SettingsState::Open {
+            message_id: MSG,
+            path,
+        };
+        let table = [
+            (
+                SettingsState::Closed,
+                Navigation::Open(Page::Timezone),
+                open(vec![Page::Main, Page::Timezone]),
+            ),
+            (
+                open(vec![Page::Main, Page::Timezone]),
+                Navigation::Back,
+                open(vec![Page::Main]),
+            ),
+            (
+                open(vec![Page::Main]),
+                Navigation::Back,
+                open(vec![Page::Main]),
+            ),
+            (
+                open(vec![Page::Main, Page::Language]),
+                Navigation::Open(Page::Language),
+                open(vec![Page::Main, Page::Language]),
+            ),
+            (
+                open(vec![Page::Main, Page::Language]),
+                Navigation::Open(Page::Main),
+                open(vec![Page::Main]),
+            ),
+            (
+                open(vec![Page::Main, Page::Sources]),
+                Navigation::Close,
+                SettingsState::Closed,
+            ),
+            // menu in another message
+            (
+                SettingsState::Open {
+                    message_id: MSG + 1,
+                    path: vec![Page::Main, Page::Sources],
+                },
+                Navigation::Back,
+                open(vec![Page::Main]),
+            ),
+        ];
+        for (i, (state, navigation, expected)) in table.into_iter().enumerate() {
+            assert_eq!(state.navigate(navigation, MSG), expected, "test table[{i}]");
+        }
+    }
+    #[tokio::test]
+    async fn test_settings_storage() {
+        let storage = SettingsStorage::new(db::memory::MemoryDb::new());
+        let chat_id = ChatId(1);
+        let get = || storage.clone().get_dialogue(chat_id);
+
+        assert_eq!(get().await.unwrap(), None);
+        storage
+            .clone()
+            .update_dialogue(chat_id, SettingsState::opened(2))
+            .await
+            .unwrap();
+        assert_eq!(get().await.unwrap(), Some(SettingsState::opened(2)));
+        storage.clone().remove_dialogue(chat_id).await.unwrap();
+        assert_eq!(get().await.unwrap(), None);
+        assert!(matches!(
+            storage.clone().remove_dialogue(chat_id).await,
+            Err(SettingsStorageError::DialogueNotFound)
+        ));
+    }
+}
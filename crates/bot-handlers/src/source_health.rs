@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use teloxide::prelude::*;
+use tokio::sync::mpsc::Receiver;
+
+use common::{admins, DateTime, LogError, UnixDateTime};
+use db::{models::SourceHealth, SourceRepo, UserRepo};
+use sources::FetchStatus;
+
+use crate::{tr, DEFAULT_USER_LANG};
+
+/// Alert admin, when source is failing for this long
+const FAILING_ALERT_THRESHOLD: Duration = Duration::from_secs(3 * 60 * 60);
+/// Errors can contain whole response, so they are truncated before saving
+const MAX_ERROR_LEN: usize = 500;
+
+/// Save results of fetching sources, and alert admin when source is failing
+/// for too long and when it recovers
+pub async fn start_source_health_job<R: SourceRepo + UserRepo>(
+    bot: Bot,
+    db: R,
+    mut rx: Receiver<FetchStatus>,
+) {
+    log::debug!("starting listen for sources fetch statuses");
+    while let Some(status) = rx.recv().await {
+        handle_fetch_status(&bot, &db, status)
+            .await
+            .log_error_msg("failed to handle source fetch status");
+    }
+}
+
+async fn handle_fetch_status<R: SourceRepo + UserRepo>(
+    bot: &Bot,
+    db: &R,
+    status: FetchStatus,
+) -> Result<()> {
+    let Some(source) = db.select_source_by_name(status.source).await? else {
+        log::error!("fetched unknown source {}", status.source);
+        return Ok(());
+    };
+    let source_id = source.source_id();
+    let now = DateTime::now();
+
+    match status.result {
+        Ok(count) => {
+            let was_alerted = db.select_source_health(source_id).await?.alerted();
+            db.save_source_fetch_succeeded(source_id, count as u32, now)
+                .await?;
+            if was_alerted {
+                log::info!(event = "source_recovered", source = source.name(); "source {} recovered", source.name());
+                send_alert(bot, db, |lang| {
+                    tr!(source_recovered_alert, lang, &source.display_name())
+                })
+                .await;
+            }
+        }
+        Err(e) => {
+            let error: String = e.chars().take(MAX_ERROR_LEN).collect();
+            db.save_source_fetch_failed(source_id, &error, now).await?;
+            let health = db.select_source_health(source_id).await?;
+            if should_alert(&health, now) {
+                let since = health.failing_since().unwrap_or(now);
+                log::warn!(
+                    event = "source_failing", source = source.name();
+                    "source {} is failing since {}: {error}",
+                    source.name(),
+                    DateTime::format(since)
+                );
+                send_alert(bot, db, |lang| {
+                    tr!(
+                        source_failing_alert,
+                        lang,
+                        &source.display_name(),
+                        &DateTime::format(since),
+                        &health.consecutive_failures().to_string(),
+                        &error
+                    )
+                })
+                .await;
+                db.save_source_alerted(source_id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Source is failing for longer than threshold, and admin is not yet alerted
+fn should_alert(health: &SourceHealth, now: UnixDateTime) -> bool {
+    !health.alerted()
+        && health
+            .failing_since()
+            .is_some_and(|since| now - since >= FAILING_ALERT_THRESHOLD.as_secs() as i64)
+}
+
+async fn admin_lang<R: UserRepo>(db: &R, chat_id: i64) -> String {
+    db.select_user(db::types::ChatId(chat_id))
+        .await
+        .ok()
+        .flatten()
+        .map(|u| u.lang().to_string())
+        .unwrap_or(DEFAULT_USER_LANG.to_string())
+}
+
+/// Send alert to all admins, `text` is called with admin's language
+async fn send_alert<R: UserRepo>(bot: &Bot, db: &R, text: impl Fn(&str) -> String) {
+    if admins().next().is_none() {
+        log::warn!(
+            "admin chats not set, skip sending alert: {}",
+            text(DEFAULT_USER_LANG)
+        );
+        return;
+    }
+    for (chat_id, _) in admins() {
+        let lang = admin_lang(db, chat_id).await;
+        bot.send_message(ChatId(chat_id), text(&lang))
+            .await
+            .log_error_msg("failed to send alert to admin");
+    }
+}
+
+/// Status of all sources for /sources
+pub(crate) fn sources_health_text(health: &[SourceHealth], lang: &str) -> String {
+    let time_or_never = |time: UnixDateTime| {
+        if time == 0 {
+            tr!(source_health_never, lang)
+        } else {
+            DateTime::format(time)
+        }
+    };
+
+    let sources = health.iter().map(|h| {
+        let mut lines = vec![
+            h.source().display_name(),
+            tr!(
+                source_health_last_success,
+                lang,
+                &time_or_never(h.last_success_at())
+            ),
+            tr!(
+                source_health_updates_found,
+                lang,
+                &h.updates_found().to_string()
+            ),
+        ];
+        if let Some(since) = h.failing_since() {
+            lines.push(tr!(
+                source_health_failing,
+                lang,
+                &h.consecutive_failures().to_string(),
+                &DateTime::format(since)
+            ));
+        }
+        if let Some((error, time)) = h.last_error() {
+            lines.push(tr!(
+                source_health_last_error,
+                lang,
+                &DateTime::format(time),
+                error
+            ));
+        }
+        lines.join("\n")
+    });
+
+    std::iter::once(tr!(source_health_header, lang))
+        .chain(sources)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
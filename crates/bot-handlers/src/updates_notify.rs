@@ -2,66 +2,76 @@ use anyhow::Result;
 use teloxide::prelude::*;
 use tokio::sync::mpsc::Receiver;
 
-use common::{DateTime, LogError};
-use db::{models::ShouldNotify, DB};
+use common::{DateTime, LogError, UnixDateTime};
+use db::{
+    models::{DeliveryMode, NewSourcesMode, ShouldNotify, Source, User, Verbosity},
+    types::Id,
+    Repository, UserRepo,
+};
 use sources::{Update, UpdatesList};
 
 use crate::keyboards::{Keyboards, NewAppKeyboardKind};
 use crate::tr;
 
-pub async fn start_updates_notify_job(bot: Bot, db: DB, mut rx: Receiver<UpdatesList>) {
+/// Notify users about updates, until channel is closed. Updates, which are
+/// already queued, are sent before returning
+pub async fn start_updates_notify_job<R: Repository>(
+    bot: Bot,
+    db: R,
+    mut rx: Receiver<UpdatesList>,
+) {
     notify_bot_update(bot.clone(), db.clone())
         .await
         .log_error_msg("failed to notify about bot update");
+    notify_new_sources(bot.clone(), db.clone())
+        .await
+        .log_error_msg("failed to notify about new sources");
 
     log::debug!("starting listen for updates");
-    // todo: graceful shutdown for updates
     while let Some(updates) = rx.recv().await {
-        log::debug!("got {} updates", updates.count());
-        db.save_source_updated_at(updates.last_update)
+        log::debug!("got {} updates of {}", updates.count(), updates.source);
+        let source_id = match db.select_source_by_name(updates.source).await {
+            Ok(Some(source)) => source.source_id(),
+            Ok(None) => {
+                log::error!("source {} not found, dropping updates", updates.source);
+                continue;
+            }
+            Err(e) => {
+                log::error!("failed to select source {}: {e}", updates.source);
+                continue;
+            }
+        };
+        db.save_source_updated_at(source_id, updates.last_update)
             .await
             .log_error_msg("failed to save source last_updated_at");
 
         for update in updates.updates {
             let app_id = update.app_id();
-            log::debug!("got update for app {}", app_id);
+            log::debug!(app_id; "got update for app {app_id}");
 
-            if let Err(e) = db.add_or_update_app(app_id, "", update.update_time()).await {
-                log::error!("failed to add app: {e}");
-                continue;
-            }
-
-            let users = match db.select_users_to_notify(app_id).await {
-                Ok(v) => v,
-                Err(e) => {
-                    log::error!("failed to select users: {e}");
-                    continue;
-                }
-            };
-            log::debug!("sending app '{app_id}' update to {} users", users.len());
-
-            for user in &users {
-                let user_id = user.user_id();
-                let chat_id = ChatId(user_id);
-                let lang = user.lang();
-                let res = match db.should_notify_user(user_id, app_id).await {
-                    Ok(s) => match s {
-                        ShouldNotify::Unspecified => {
-                            send_suggest_update(bot.clone(), chat_id, &update, lang).await
-                        }
-                        ShouldNotify::Notify => {
-                            send_update(bot.clone(), chat_id, &update, lang).await
-                        }
-                        ShouldNotify::Ignore => {
-                            log::debug!("ignoring update {app_id} for user {user_id}");
-                            continue;
-                        }
-                    },
+            let notifications =
+                match select_update_notifications(&db, source_id, app_id, update.update_time())
+                    .await
+                {
+                    Ok(v) => v,
                     Err(e) => {
-                        log::error!("failed to check, if should notify user {user_id}: {e}");
+                        log::error!("failed to select users to notify: {e}");
                         continue;
                     }
                 };
+            log::debug!(app_id; "sending app '{app_id}' update to {} users", notifications.len());
+
+            for (user, kind) in &notifications {
+                let chat_id = ChatId(user.user_id());
+                let res = match kind {
+                    UpdateMsgKind::NewApp => {
+                        send_suggest_update(bot.clone(), chat_id, source_id, &update, user).await
+                    }
+                    UpdateMsgKind::Update => {
+                        send_update(bot.clone(), chat_id, source_id, &update, user).await
+                    }
+                };
+                record_notification(&res);
                 if let Err(e) = res {
                     match e {
                         UpdateError::BotBlocked(chat_id) => {
@@ -79,35 +89,62 @@ pub async fn start_updates_notify_job(bot: Bot, db: DB, mut rx: Receiver<Updates
             }
         }
 
-        db.save_all_users_last_notified(DateTime::now())
+        db.save_all_users_last_notified(source_id, DateTime::now())
             .await
             .log_error_msg("failed to save all users last_notified_at");
     }
+    log::debug!("updates channel is closed, stop notifying");
+}
+
+/// Save update of app and select users to notify about it, with kind of
+/// message for each one. Users, who ignore app, are skipped
+async fn select_update_notifications<R: Repository>(
+    db: &R,
+    source_id: Id,
+    app_id: &str,
+    update_time: UnixDateTime,
+) -> Result<Vec<(User, UpdateMsgKind)>, db::Error> {
+    db.add_or_update_app(source_id, app_id, "", update_time)
+        .await?;
+    let users = db.select_users_to_notify(source_id, app_id).await?;
+
+    let mut notifications = Vec::with_capacity(users.len());
+    for user in users {
+        let user_id = user.user_id();
+        let kind = match db.should_notify_user(user_id, source_id, app_id).await {
+            Ok(ShouldNotify::Unspecified) => UpdateMsgKind::NewApp,
+            Ok(ShouldNotify::Notify) => UpdateMsgKind::Update,
+            Ok(ShouldNotify::Ignore) => {
+                log::debug!("ignoring update {app_id} for user {user_id}");
+                continue;
+            }
+            Err(e) => {
+                log::error!("failed to check, if should notify user {user_id}: {e}");
+                continue;
+            }
+        };
+        notifications.push((user, kind));
+    }
+    Ok(notifications)
 }
 
 async fn send_suggest_update(
     bot: Bot,
     chat_id: ChatId,
+    source_id: Id,
     update: &Update,
-    lang: &str,
+    user: &User,
 ) -> Result<(), UpdateError> {
-    let mut text = vec![tr!(new_app_msg, lang) + "\n"];
-    if let Some(description) = update.description() {
-        text.push(format!("\n{description}\n"));
-    }
-    if let Some(url) = update.description_link() {
-        text.push(url.to_string());
-    } else if let Some(url) = update.update_link() {
-        text.push(url.to_string());
-    }
-
-    bot.send_message(chat_id, text.join(""))
+    let lang = user.lang();
+    bot.send_message(chat_id, render_update(update, UpdateMsgKind::NewApp, user))
         .reply_markup(Keyboards::update(
+            source_id,
             update.app_id(),
             update.update_link().clone(),
             NewAppKeyboardKind::Both,
             lang,
         ))
+        .disable_notification(user.delivery_mode() == DeliveryMode::Silent)
         .await
         .map_bot_blocked_error(chat_id)
 }
@@ -115,29 +152,87 @@ async fn send_suggest_update(
 async fn send_update(
     bot: Bot,
     chat_id: ChatId,
+    source_id: Id,
     update: &Update,
-    lang: &str,
+    user: &User,
 ) -> Result<(), UpdateError> {
-    let app_id = update.app_id();
-    let mut text = vec![tr!(new_update_msg, lang, app_id) + "\n"];
-    if let Some(url) = update.update_link() {
-        text.push(url.to_string());
-    } else if let Some(url) = update.description_link() {
-        text.push(url.to_string());
-    }
-
-    bot.send_message(chat_id, text.join(""))
+    let lang = user.lang();
+    bot.send_message(chat_id, render_update(update, UpdateMsgKind::Update, user))
         .reply_markup(Keyboards::update(
-            app_id,
+            source_id,
+            update.app_id(),
             update.update_link().clone(),
             NewAppKeyboardKind::NotifyEnabled,
             lang,
         ))
+        .disable_notification(user.delivery_mode() == DeliveryMode::Silent)
         .await
         .map_bot_blocked_error(chat_id)
 }
 
-async fn notify_bot_update(bot: Bot, db: DB) -> Result<()> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpdateMsgKind {
+    /// User not yet decided, whether to track this app
+    NewApp,
+    /// User tracks this app
+    Update,
+}
+
+/// Render text of notification about update according to user's settings
+fn render_update(update: &Update, kind: UpdateMsgKind, user: &User) -> String {
+    let app_id = update.app_id();
+    let lang = user.lang();
+    match user.verbosity() {
+        Verbosity::Minimal => match kind {
+            UpdateMsgKind::NewApp => tr!(new_app_msg_minimal, lang, app_id),
+            UpdateMsgKind::Update => tr!(new_update_msg_minimal, lang, app_id),
+        },
+        Verbosity::Normal => match kind {
+            UpdateMsgKind::NewApp => {
+                let mut text = vec![tr!(new_app_msg, lang) + "\n"];
+                if let Some(description) = update.description() {
+                    text.push(format!("\n{description}\n"));
+                }
+                if let Some(url) = update.description_link() {
+                    text.push(url.to_string());
+                } else if let Some(url) = update.update_link() {
+                    text.push(url.to_string());
+                }
+                text.join("")
+            }
+            UpdateMsgKind::Update => {
+                let mut text = vec![tr!(new_update_msg, lang, app_id) + "\n"];
+                if let Some(url) = update.update_link() {
+                    text.push(url.to_string());
+                } else if let Some(url) = update.description_link() {
+                    text.push(url.to_string());
+                }
+                text.join("")
+            }
+        },
+        Verbosity::Detailed => {
+            let time = DateTime::format_with_offset(update.update_time(), user.utc_offset());
+            let mut text = vec![match kind {
+                UpdateMsgKind::NewApp => tr!(new_app_msg_detailed, lang, app_id, &time),
+                UpdateMsgKind::Update => tr!(new_update_msg_detailed, lang, app_id, &time),
+            }];
+            if let Some(description) = update.description() {
+                text.push(format!("\n{description}\n"));
+            }
+            // links are not passed to templates, because fluent wraps
+            // arguments in unicode isolation marks
+            if let Some(url) = update.description_link() {
+                text.push(format!("{} {url}", tr!(update_description_link, lang)));
+            }
+            if let Some(url) = update.update_link() {
+                text.push(format!("{} {url}", tr!(update_link, lang)));
+            }
+            text.join("\n")
+        }
+    }
+}
+
+async fn notify_bot_update<R: Repository>(bot: Bot, db: R) -> Result<()> {
     let users = db.select_users_to_notify_about_bot_update().await?;
     log::debug!("sending bot update notification to {} users", users.len());
 
@@ -148,12 +243,13 @@ async fn notify_bot_update(bot: Bot, db: DB) -> Result<()> {
         let chat_id = ChatId(user_id);
         let lang = u.lang();
         let text = crate::utils::escape(tr!(bot_updated, lang));
-        if let Err(e) = bot
+        let res = bot
             .send_message(chat_id, text)
             .parse_mode(teloxide::types::ParseMode::MarkdownV2)
             .await
-            .map_bot_blocked_error(chat_id)
-        {
+            .map_bot_blocked_error(chat_id);
+        record_notification(&res);
+        if let Err(e) = res {
             match e {
                 UpdateError::BotBlocked(chat_id) => {
                     handle_bot_blocked(&db, chat_id, ChatUnavailableError::BotBlocked).await;
@@ -186,8 +282,82 @@ async fn notify_bot_update(bot: Bot, db: DB) -> Result<()> {
     Ok(())
 }
 
+/// Subscribe or suggest subscribing to sources, about which user didn't decide
+/// yet, depending on user's setting for new sources
+async fn notify_new_sources<R: Repository>(bot: Bot, db: R) -> Result<()> {
+    for source in db.select_sources().await? {
+        let users = db
+            .select_users_to_notify_about_source(source.source_id())
+            .await?;
+        if users.is_empty() {
+            continue;
+        }
+        log::debug!(
+            "notifying {} users about new source {}",
+            users.len(),
+            source.name()
+        );
+
+        for user in users {
+            let chat_id = ChatId(user.user_id());
+            let subscribe = user.new_sources() == NewSourcesMode::Subscribe;
+            let res = send_new_source(bot.clone(), chat_id, &source, &user, subscribe)
+                .await
+                .map_bot_blocked_error(chat_id);
+            record_notification(&res);
+            match res {
+                Ok(()) => {}
+                Err(UpdateError::BotBlocked(chat_id)) => {
+                    handle_bot_blocked(&db, chat_id, ChatUnavailableError::BotBlocked).await;
+                    continue;
+                }
+                Err(UpdateError::UserDeactivated(chat_id)) => {
+                    handle_bot_blocked(&db, chat_id, ChatUnavailableError::UserDeactivated).await;
+                    continue;
+                }
+                Err(UpdateError::RequestError(e)) => {
+                    log::error!("failed to send new source to user {chat_id}: {e}");
+                    continue;
+                }
+            }
+            // when asked, save "unsubscribed" so user will not be asked again
+            db.save_user_subscribed(user.user_id(), source.source_id(), subscribe)
+                .await
+                .log_error_msg("failed to save user subscription to new source");
+        }
+    }
+    Ok(())
+}
+
+async fn send_new_source(
+    bot: Bot,
+    chat_id: ChatId,
+    source: &Source,
+    user: &User,
+    subscribe: bool,
+) -> ResponseResult<()> {
+    let lang = user.lang();
+    let name = source.display_name();
+    let text = if subscribe {
+        tr!(new_source_subscribed_msg, lang, &name)
+    } else {
+        tr!(new_source_msg, lang, &name)
+    };
+    let text = [text, source.description().to_string()].join("\n\n");
+    let msg = bot
+        .send_message(chat_id, text)
+        .disable_notification(user.delivery_mode() == DeliveryMode::Silent);
+    if subscribe {
+        msg.await?;
+    } else {
+        msg.reply_markup(Keyboards::new_source(source.source_id(), lang))
+            .await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
-enum UpdateError {
+pub(crate) enum UpdateError {
     #[error("bot blocked by user {0}")]
     BotBlocked(ChatId),
     #[error("user {0} deactivated")]
@@ -197,7 +367,7 @@ enum UpdateError {
     RequestError(#[from] teloxide::RequestError),
 }
 
-trait MapBotBlockedError {
+pub(crate) trait MapBotBlockedError {
     fn map_bot_blocked_error(self, chat_id: ChatId) -> Result<(), UpdateError>;
 }
 
@@ -218,18 +388,123 @@ impl<R> MapBotBlockedError for Result<R, teloxide::RequestError> {
     }
 }
 
+/// Count result of sending notification in metrics
+pub(crate) fn record_notification(res: &Result<(), UpdateError>) {
+    let status = match res {
+        Ok(()) => "sent",
+        Err(UpdateError::BotBlocked(_) | UpdateError::UserDeactivated(_)) => "blocked",
+        Err(UpdateError::RequestError(_)) => "failed",
+    };
+    metrics::counter!("notifications_total", "status" => status).increment(1);
+}
+
 /// Save that bot can't send message to user
-async fn handle_bot_blocked(db: &DB, chat_id: ChatId, kind: ChatUnavailableError) {
-    log::info!(tg = true; "{kind}, chat_id = {chat_id}");
+pub(crate) async fn handle_bot_blocked(
+    db: &impl UserRepo,
+    chat_id: ChatId,
+    kind: ChatUnavailableError,
+) {
+    log::info!(tg = true, event = "user_left", user_id = chat_id.0; "{kind}, chat_id = {chat_id}");
     db.save_user_unavailable(chat_id, true)
         .await
         .log_error_msg("failed to save user unavailable");
 }
 
 #[derive(Debug, thiserror::Error)]
-enum ChatUnavailableError {
+pub(crate) enum ChatUnavailableError {
     #[error("bot blocked by user")]
     BotBlocked,
     #[error("user deactivated")]
     UserDeactivated,
 }
+
+#[cfg(test)]
+mod tests {
+    use db::{memory::MemoryDb, AppRepo, SubscriptionRepo};
+
+    use super::*;
+
+    const SOURCE_ID: Id = 1;
+
+    async fn add_user(db: &MemoryDb, user_id: i64, subscribed: bool, bot_blocked: bool) {
+        let user = User::builder()
+            .user_id(user_id)
+            .last_notified_at(100)
+            .bot_blocked(bot_blocked)
+            .build();
+        db.add_user(user).await.unwrap();
+        db.save_user_subscribed(user_id, SOURCE_ID, subscribed)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_select_update_notifications() {
+        let db = MemoryDb::new();
+        add_user(&db, 1, true, false).await;
+        add_user(&db, 2, true, false).await;
+        add_user(&db, 3, true, false).await;
+        add_user(&db, 4, false, false).await;
+        add_user(&db, 5, true, true).await;
+        db.save_should_notify_user(2, SOURCE_ID, "app", ShouldNotify::Notify)
+            .await
+            .unwrap();
+        db.save_should_notify_user(3, SOURCE_ID, "app", ShouldNotify::Ignore)
+            .await
+            .unwrap();
+
+        for (i, (update_time, expected)) in [
+            // not newer, than last notification
+            (100, vec![]),
+            (
+                200,
+                vec![(1, UpdateMsgKind::NewApp), (2, UpdateMsgKind::Update)],
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let notifications = select_update_notifications(&db, SOURCE_ID, "app", update_time)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(u, kind)| (u.user_id(), kind))
+                .collect::<Vec<_>>();
+            assert_eq!(notifications, expected, "test table[{i}]");
+        }
+
+        // subscribed users are not notified about same update again
+        db.save_all_users_last_notified(SOURCE_ID, 200)
+            .await
+            .unwrap();
+        assert!(select_update_notifications(&db, SOURCE_ID, "app", 200)
+            .await
+            .unwrap()
+            .is_empty());
+        let user = db.select_user(4).await.unwrap().unwrap();
+        assert_eq!(user.last_notified_at(), 100);
+    }
+
+    #[test]
+    fn test_render_detailed() {
+        let user = User::builder()
+            .user_id(1)
+            .lang("en".to_string())
+            .verbosity(Verbosity::Detailed)
+            .build();
+        let time = DateTime::format_with_offset(0, 0);
+        for (i, (kind, title)) in [
+            (UpdateMsgKind::NewApp, "New app to track updates: "),
+            (UpdateMsgKind::Update, "Update for "),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let text = render_update(&Update::default(), kind, &user);
+            assert!(text.starts_with(title), "test table[{i}]");
+            assert!(text.contains(&time), "test table[{i}]");
+            // arguments are passed to referenced messages
+            assert!(!text.contains("{$"), "test table[{i}]");
+        }
+    }
+}
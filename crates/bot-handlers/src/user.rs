@@ -1,9 +1,27 @@
-use teloxide::{prelude::Requester, types::ChatFullInfoKind, Bot};
+use std::time::Duration;
 
-use common::LogError;
-use db::DB;
+use teloxide::{
+    dispatching::dialogue::serializer::{Json, Serializer},
+    prelude::Requester,
+    types::{ChatFullInfoKind, Update},
+    Bot,
+};
+use tokio::time::MissedTickBehavior;
 
-pub async fn run_collect_user_names_job(bot: Bot, db: DB) -> Result<(), UsersCollectError> {
+use common::{is_admin_chat_id, DateTime, LogError};
+use db::{
+    models::{ShouldNotify, User},
+    AppRepo, BroadcastRepo, SubscriptionRepo, UserRepo,
+};
+
+use i18n::{tr, tr_literal};
+
+use crate::{bot_admin_messages::format_time, settings::SettingsState};
+
+pub async fn run_collect_user_names_job<R: UserRepo>(
+    bot: Bot,
+    db: R,
+) -> Result<(), UsersCollectError> {
     for u in db.select_all_users().await? {
         let chat = bot.get_chat(u.tg_user_id()).await?;
         if let ChatFullInfoKind::Private(chat) = chat.kind {
@@ -24,6 +42,149 @@ pub async fn run_collect_user_names_job(bot: Bot, db: DB) -> Result<(), UsersCol
     Ok(())
 }
 
+/// Check that update is not from user, banned by admin
+pub async fn is_not_banned<R: UserRepo>(update: Update, db: R) -> bool {
+    let Some(user) = update.from() else {
+        return true;
+    };
+    if is_admin_chat_id(user.id.0 as i64) {
+        return true;
+    }
+    match db.is_user_banned(user.id).await {
+        Ok(banned) => {
+            if banned {
+                log::debug!("ignoring update from banned user {}", user.id);
+            }
+            !banned
+        }
+        Err(e) => {
+            log::error!("failed to check if user banned: {e}");
+            true
+        }
+    }
+}
+
+/// Every `interval` delete users, who blocked bot more than `blocked_for`
+/// ago
+pub async fn start_retention_job<R: UserRepo>(db: R, blocked_for: Duration, interval: Duration) {
+    log::debug!("starting deleting users blocked for {blocked_for:?}");
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        delete_blocked_users(&db, blocked_for).await;
+    }
+}
+
+async fn delete_blocked_users(db: &impl UserRepo, blocked_for: Duration) {
+    let blocked_before = DateTime::now() - blocked_for.as_secs() as i64;
+    match db.delete_users_blocked_before(blocked_before).await {
+        Ok(ids) if ids.is_empty() => {}
+        Ok(ids) => {
+            log::info!(tg = true, event = "users_purged"; "Deleted {} users, who blocked bot", ids.len());
+            log::debug!("deleted blocked users: {ids:?}");
+        }
+        Err(e) => log::error!("failed to delete blocked users: {e}"),
+    }
+}
+
+/// Everything stored about user, for /mydata. `settings` is stored state of
+/// /settings menu
+pub(crate) async fn user_data_text(
+    db: &(impl UserRepo + AppRepo + SubscriptionRepo + BroadcastRepo),
+    user: &User,
+    settings: Option<&SettingsState>,
+    lang: &str,
+) -> Result<String, db::Error> {
+    let or_none = |list: Vec<String>| {
+        if list.is_empty() {
+            tr!(user_info_none, lang)
+        } else {
+            list.join(", ")
+        }
+    };
+
+    let subscriptions = db
+        .select_user_subscriptions(user.user_id())
+        .await?
+        .iter()
+        .filter(|s| s.subscribed())
+        .map(|s| s.source().display_name())
+        .collect();
+    let (followed, ignored): (Vec<_>, Vec<_>) = db
+        .select_user_updates(user.user_id())
+        .await?
+        .into_iter()
+        .partition(|u| u.should_notify() == ShouldNotify::Notify);
+    let app_ids = |updates: Vec<db::models::UserUpdate>| {
+        updates.iter().map(|u| u.app_id().to_string()).collect()
+    };
+    let version_notified = match db.select_user_version_notified(user.user_id()).await? {
+        0 => tr!(user_info_none, lang),
+        version => version.to_string(),
+    };
+    let broadcasts = db
+        .select_user_broadcast_deliveries(user.user_id())
+        .await?
+        .into_iter()
+        .map(|(broadcast_id, status)| format!("#{broadcast_id} {}", status.as_str()))
+        .collect();
+    // shown as stored
+    let settings_menu = settings
+        .and_then(|s| Json.serialize(s).ok())
+        .map_or(tr!(user_info_none, lang), |bytes| {
+            String::from_utf8_lossy(&bytes).into_owned()
+        });
+    let settings = format!(
+        "{}, {}, {}, {}",
+        user.verbosity().as_str(),
+        user.delivery_mode().as_str(),
+        DateTime::format_utc_offset(user.utc_offset()),
+        user.new_sources().as_str(),
+    );
+
+    let fields = [
+        ("user-info-id", user.user_id().to_string()),
+        (
+            "mydata-username",
+            user.username()
+                .map_or(tr!(user_info_none, lang), |u| format!("@{u}")),
+        ),
+        (
+            "mydata-name",
+            user.name()
+                .map_or(tr!(user_info_none, lang), str::to_string),
+        ),
+        ("user-info-lang", user.lang().to_string()),
+        ("user-info-joined", format_time(user.joined_at(), lang)),
+        (
+            "user-info-last-notified",
+            format_time(user.last_notified_at(), lang),
+        ),
+        ("mydata-version-notified", version_notified),
+        (
+            "user-info-bot-blocked",
+            if user.bot_blocked_at() == 0 {
+                tr!(user_info_no, lang)
+            } else {
+                format_time(user.bot_blocked_at(), lang)
+            },
+        ),
+        ("user-info-settings", settings),
+        ("user-info-subscriptions", or_none(subscriptions)),
+        ("user-info-apps", or_none(app_ids(followed))),
+        ("mydata-ignored-apps", or_none(app_ids(ignored))),
+        ("mydata-broadcasts", or_none(broadcasts)),
+        ("mydata-settings-menu", settings_menu),
+    ]
+    .into_iter()
+    .map(|(tr_key, value)| format!("{}: {value}", tr_literal!(tr_key, lang)))
+    .collect::<Vec<_>>()
+    .join("\n");
+
+    Ok([tr!(mydata_header, lang), fields, tr!(mydata_footer, lang)].join("\n\n"))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum UsersCollectError {
     #[error(transparent)]
@@ -39,3 +200,73 @@ pub(crate) fn get_chat_name(first_name: Option<&str>, last_name: Option<&str>) -
         (None, None) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use db::{memory::MemoryDb, models::DeliveryStatus};
+
+    use super::*;
+    use crate::keyboards::SettingsPage;
+
+    #[tokio::test]
+    async fn test_user_data_text() {
+        let db = MemoryDb::new();
+        let user = User::builder()
+            .user_id(1)
+            .lang("en".to_string())
+            .username("user".to_string())
+            .build();
+        db.add_user(user.clone()).await.unwrap();
+        db.save_should_notify_user(1, 1, "followed", ShouldNotify::Notify)
+            .await
+            .unwrap();
+        db.save_should_notify_user(1, 1, "ignored", ShouldNotify::Ignore)
+            .await
+            .unwrap();
+
+        let text = user_data_text(&db, &user, None, "en").await.unwrap();
+        for (i, line) in [
+            "ID: 1",
+            "Username: @user",
+            "Name: none",
+            "Last notified about bot version: none",
+            "Blocked bot: no",
+            "Subscriptions: none",
+            "Followed apps: followed",
+            "Ignored apps: ignored",
+            "Broadcasts: none",
+            "Settings menu: none",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert!(text.lines().any(|l| l == line), "test table[{i}]: {text}");
+        }
+
+        db.save_user_unavailable(1, true).await.unwrap();
+        db.save_broadcast_delivery(2, 1, DeliveryStatus::Sent)
+            .await
+            .unwrap();
+        db.save_broadcast_delivery(3, 1, DeliveryStatus::Blocked)
+            .await
+            .unwrap();
+        let user = db.select_user(1).await.unwrap().unwrap();
+        let settings = SettingsState::Open {
+            message_id: 5,
+            path: vec![SettingsPage::Main],
+        };
+        let text = user_data_text(&db, &user, Some(&settings), "en")
+            .await
+            .unwrap();
+        for (i, line) in [
+            format!("Blocked bot: {}", DateTime::format(user.bot_blocked_at())),
+            "Broadcasts: #2 sent, #3 blocked".to_string(),
+            r#"Settings menu: {"Open":{"message_id":5,"path":["Main"]}}"#.to_string(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert!(text.lines().any(|l| l == line), "test table[{i}]: {text}");
+        }
+    }
+}
@@ -1,11 +1,81 @@
-use chrono::Utc;
+use chrono::{FixedOffset, Utc};
 
 pub type UnixDateTime = i64;
 
+const COMPACT_FORMAT: &str = "%Y%m%d-%H%M%S";
+
 pub struct DateTime;
 
 impl DateTime {
     pub fn now() -> UnixDateTime {
         Utc::now().timestamp()
     }
+    /// Format unix time as `YYYY-MM-DD HH:MM UTC`
+    pub fn format(time: UnixDateTime) -> String {
+        Self::format_with_offset(time, 0)
+    }
+    /// Format unix time as `YYYYMMDD-HHMMSS` in UTC, e.g. for file names
+    pub fn format_compact(time: UnixDateTime) -> String {
+        match chrono::DateTime::from_timestamp(time, 0) {
+            Some(t) => t.format(COMPACT_FORMAT).to_string(),
+            None => time.to_string(),
+        }
+    }
+    /// Parse time, formatted with [`DateTime::format_compact`]
+    pub fn parse_compact(s: &str) -> Option<UnixDateTime> {
+        chrono::NaiveDateTime::parse_from_str(s, COMPACT_FORMAT)
+            .ok()
+            .map(|t| t.and_utc().timestamp())
+    }
+    /// Format unix time in timezone `utc_offset` (minutes from UTC) as
+    /// `YYYY-MM-DD HH:MM UTC+H`
+    pub fn format_with_offset(time: UnixDateTime, utc_offset: i32) -> String {
+        let Some(offset) = FixedOffset::east_opt(utc_offset * 60) else {
+            return Self::format(time);
+        };
+        match chrono::DateTime::from_timestamp(time, 0) {
+            Some(t) => format!(
+                "{} {}",
+                t.with_timezone(&offset).format("%Y-%m-%d %H:%M"),
+                Self::format_utc_offset(utc_offset)
+            ),
+            None => time.to_string(),
+        }
+    }
+    /// Format timezone `utc_offset` (minutes from UTC) as `UTC`, `UTC+3`,
+    /// `UTC-9:30`
+    pub fn format_utc_offset(utc_offset: i32) -> String {
+        let sign = if utc_offset < 0 { '-' } else { '+' };
+        let (hours, minutes) = (utc_offset.abs() / 60, utc_offset.abs() % 60);
+        match (hours, minutes) {
+            (0, 0) => "UTC".to_string(),
+            (h, 0) => format!("UTC{sign}{h}"),
+            (h, m) => format!("UTC{sign}{h}:{m:02}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_with_offset() {
+        let table = [
+            (0, "2024-01-01 12:00 UTC"),
+            (180, "2024-01-01 15:00 UTC+3"),
+            (-570, "2024-01-01 02:30 UTC-9:30"),
+        ];
+        for (offset, expected) in table {
+            assert_eq!(DateTime::format_with_offset(1704110400, offset), expected);
+        }
+    }
+
+    #[test]
+    fn test_compact() {
+        let s = DateTime::format_compact(1704110405);
+        assert_eq!(s, "20240101-120005");
+        assert_eq!(DateTime::parse_compact(&s), Some(1704110405));
+        assert_eq!(DateTime::parse_compact("2024-01-01"), None);
+    }
 }
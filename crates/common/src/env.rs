@@ -1,6 +1,10 @@
-use std::sync::LazyLock;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{LazyLock, OnceLock},
+};
 
-use dotenvy_macro::dotenv;
+use serde::Deserialize;
 
 pub(crate) static VERSION: LazyLock<u32> = LazyLock::new(|| {
     env!("BOT_VERSION")
@@ -14,19 +18,69 @@ pub fn version() -> u32 {
     *VERSION
 }
 
-pub(crate) static ADMIN_CHAT_ID: LazyLock<Option<i64>> = LazyLock::new(|| {
-    let s = dotenv!("ADMIN_CHAT_ID");
-    if s.is_empty() {
-        None
-    } else {
-        Some(s.parse().expect("invalid ADMIN_CHAT_ID"))
+/// Role of admin. Roles are ordered, each next role can do everything
+/// previous can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    /// Can view stats, users and sources
+    Viewer,
+    /// Can also fetch sources
+    Operator,
+    /// Can also send broadcasts and ban users
+    Owner,
+}
+
+impl AdminRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Owner => "owner",
+        }
     }
-});
+}
+
+impl FromStr for AdminRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "operator" => Ok(Self::Operator),
+            "owner" => Ok(Self::Owner),
+            _ => Err(format!("unknown admin role {s:?}")),
+        }
+    }
+}
 
-pub fn admin_chat_id() -> Option<i64> {
-    *ADMIN_CHAT_ID
+static ADMINS: OnceLock<HashMap<i64, AdminRole>> = OnceLock::new();
+
+/// Set admins from config. Should be called once, before starting bot
+pub fn init_admins(admins: HashMap<i64, AdminRole>) {
+    if ADMINS.set(admins).is_err() {
+        log::error!("admins are already initialized");
+    }
+}
+
+/// Admin chats with their roles. Empty, if not initialized
+pub fn admins() -> impl Iterator<Item = (i64, AdminRole)> {
+    ADMINS
+        .get()
+        .into_iter()
+        .flatten()
+        .map(|(&id, &role)| (id, role))
+}
+
+pub fn admin_role(id: i64) -> Option<AdminRole> {
+    ADMINS.get().and_then(|a| a.get(&id)).copied()
 }
 
 pub fn is_admin_chat_id(id: i64) -> bool {
-    admin_chat_id().is_some_and(|i| i == id)
+    admin_role(id).is_some()
+}
+
+/// Check that chat is admin with at least `role`
+pub fn has_admin_role(id: i64, role: AdminRole) -> bool {
+    admin_role(id).is_some_and(|r| r >= role)
 }
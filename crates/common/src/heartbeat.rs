@@ -0,0 +1,54 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{DateTime, UnixDateTime};
+
+/// Deadline, until which job should report again. Job, which missed deadline,
+/// is considered stalled
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    deadline: Arc<AtomicI64>,
+}
+
+impl Heartbeat {
+    pub fn new(first_within: Duration) -> Self {
+        let s = Self {
+            deadline: Arc::default(),
+        };
+        s.beat(first_within);
+        s
+    }
+    /// Report that job is alive, and will report again within `next_within`
+    pub fn beat(&self, next_within: Duration) {
+        let deadline = DateTime::now().saturating_add(next_within.as_secs() as i64);
+        self.deadline.store(deadline, Ordering::Relaxed);
+    }
+    pub fn is_stalled(&self) -> bool {
+        self.is_stalled_at(DateTime::now())
+    }
+    pub fn is_stalled_at(&self, now: UnixDateTime) -> bool {
+        now > self.deadline.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat() {
+        let hb = Heartbeat::new(Duration::from_secs(10));
+        let now = DateTime::now();
+        assert!(!hb.is_stalled_at(now));
+        assert!(hb.is_stalled_at(now + 11));
+
+        hb.beat(Duration::from_secs(60));
+        assert!(!hb.is_stalled_at(now + 11));
+        assert!(hb.is_stalled_at(now + 61));
+    }
+}
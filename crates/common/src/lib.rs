@@ -1,9 +1,13 @@
 mod datetime;
 mod env;
+mod heartbeat;
 mod log;
+mod text;
 mod tokio;
 
 pub use datetime::*;
 pub use env::*;
+pub use heartbeat::*;
 pub use log::*;
+pub use text::*;
 pub use tokio::*;
@@ -0,0 +1,40 @@
+/// Length of text, as telegram counts it for limits of messages: in UTF-16
+/// code units, so e.g. emoji can take 2 of them
+pub fn tg_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Longest prefix of `s`, which [`tg_len`] is at most `max`. Chars are not
+/// split
+pub fn tg_truncate(s: &str, max: usize) -> &str {
+    let mut len = 0;
+    for (i, c) in s.char_indices() {
+        len += c.len_utf16();
+        if len > max {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tg_truncate() {
+        let table = [
+            ("abc", 3, "abc"),
+            ("abcdef", 4, "abcd"),
+            ("бббб", 3, "ббб"),
+            ("😀😀😀", 4, "😀😀"),
+            // emoji is not split
+            ("😀😀😀", 5, "😀😀"),
+            ("a😀", 2, "a"),
+        ];
+        for (i, (s, max, expected)) in table.into_iter().enumerate() {
+            assert_eq!(tg_truncate(s, max), expected, "test table[{i}]");
+        }
+        assert_eq!(tg_len(&"😀".repeat(10)), 20);
+    }
+}
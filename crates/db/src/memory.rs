@@ -0,0 +1,762 @@
+//! In-memory storage with same behavior as [`DB`](crate::DB), for tests of
+//! handlers. Data is lost, when last clone is dropped
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use common::{DateTime, UnixDateTime};
+use sqlx::error::{DatabaseError, ErrorKind};
+
+use crate::{
+    models::{
+        Broadcast, BroadcastProgress, BroadcastStatus, BroadcastText, DeliveryMode, DeliveryStatus,
+        NewSourcesMode, ShouldNotify, Source, SourceHealth, SourceSubscription, Stats, User,
+        UserUpdate, Verbosity,
+    },
+    repo::{
+        AdminRepo, AppRepo, BroadcastRepo, DialogueRepo, SourceRepo, SubscriptionRepo, UserRepo,
+    },
+    types::{Id, UserId},
+    Error, Result,
+};
+
+#[derive(Debug, Clone)]
+pub struct MemoryDb {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    users: BTreeMap<Id, User>,
+    /// Last version of bot, about which user was notified
+    versions_notified: HashMap<Id, u32>,
+    /// Last update time of app by (`source_id`, `app_id`)
+    apps: HashMap<(Id, String), UnixDateTime>,
+    /// Choices of users about apps by (`user_id`, `source_id`, `app_id`)
+    should_notify: HashMap<(Id, Id, String), ShouldNotify>,
+    /// Subscriptions by (`user_id`, `source_id`)
+    subscriptions: BTreeMap<(Id, Id), bool>,
+    /// Last notification about source by (`user_id`, `source_id`), same keys
+    /// as `subscriptions`
+    notified_at: HashMap<(Id, Id), UnixDateTime>,
+    sources: BTreeMap<Id, Source>,
+    /// Results of fetching sources by `source_id`, without fetched sources
+    health: HashMap<Id, SourceHealth>,
+    /// Serialized dialogues by `chat_id`
+    dialogues: HashMap<Id, Vec<u8>>,
+    broadcasts: BTreeMap<Id, Broadcast>,
+    /// Texts of broadcasts by `broadcast_id`, ordered by language
+    broadcast_texts: HashMap<Id, Vec<BroadcastText>>,
+    /// Deliveries of broadcasts by (`user_id`, `broadcast_id`)
+    deliveries: BTreeMap<(Id, Id), DeliveryStatus>,
+}
+
+/// Violation of unique constraint, same kind as returned by databases
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct UniqueViolation(String);
+
+impl DatabaseError for UniqueViolation {
+    fn message(&self) -> &str {
+        &self.0
+    }
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::UniqueViolation
+    }
+}
+
+impl Default for MemoryDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryDb {
+    /// Storage with the only source, as after migrations
+    pub fn new() -> Self {
+        let db = Self {
+            state: Default::default(),
+        };
+        db.add_source(1, "tg@alexstranniklite", "Modified Android apps");
+        db
+    }
+    pub fn add_source(&self, source_id: Id, name: &str, description: &str) {
+        self.state().sources.insert(
+            source_id,
+            Source {
+                source_id,
+                name: name.to_string(),
+                description: description.to_string(),
+                last_updated_at: 0,
+            },
+        );
+    }
+    fn state(&self) -> MutexGuard<'_, State> {
+        // state is consistent after every operation, so it can be used after
+        // panic in other test thread
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn update_user(&self, user_id: impl Into<UserId>, update: impl FnOnce(&mut User)) {
+        let id: Id = user_id.into().into();
+        if let Some(user) = self.state().users.get_mut(&id) {
+            update(user);
+        }
+    }
+}
+
+impl State {
+    fn is_subscribed(&self, user_id: Id, source_id: Id) -> bool {
+        self.subscriptions
+            .get(&(user_id, source_id))
+            .copied()
+            .unwrap_or_default()
+    }
+    /// New subscription is notified since user's last notification
+    fn save_subscribed(&mut self, user_id: Id, source_id: Id, subscribed: bool) {
+        let last_notified_at = self
+            .users
+            .get(&user_id)
+            .map(|u| u.last_notified_at)
+            .unwrap_or_default();
+        self.subscriptions.insert((user_id, source_id), subscribed);
+        self.notified_at
+            .entry((user_id, source_id))
+            .or_insert(last_notified_at);
+    }
+    /// Health of source, not fetched source is healthy
+    fn source_health(&self, source: &Source) -> SourceHealth {
+        let mut health = self
+            .health
+            .get(&source.source_id)
+            .cloned()
+            .unwrap_or_else(|| SourceHealth {
+                source: source.clone(),
+                last_success_at: 0,
+                last_error: String::new(),
+                last_error_at: 0,
+                failing_since: 0,
+                consecutive_failures: 0,
+                updates_found: 0,
+                alerted: false,
+            });
+        health.source = source.clone();
+        health
+    }
+    /// Update health of source, if it exists
+    fn update_health(&mut self, source_id: Id, update: impl FnOnce(&mut SourceHealth)) {
+        let Some(source) = self.sources.get(&source_id) else {
+            return;
+        };
+        let mut health = self.source_health(source);
+        update(&mut health);
+        self.health.insert(source_id, health);
+    }
+    fn is_broadcast_recipient(&self, broadcast_id: Id, user: &User) -> bool {
+        !user.bot_blocked
+            && !user.banned
+            && !self.deliveries.contains_key(&(user.user_id, broadcast_id))
+    }
+    fn delete_user(&mut self, user_id: Id) -> bool {
+        self.versions_notified.remove(&user_id);
+        self.dialogues.remove(&user_id);
+        self.should_notify.retain(|(id, _, _), _| *id != user_id);
+        self.subscriptions.retain(|(id, _), _| *id != user_id);
+        self.notified_at.retain(|(id, _), _| *id != user_id);
+        self.deliveries.retain(|(id, _), _| *id != user_id);
+        self.users.remove(&user_id).is_some()
+    }
+}
+
+impl UserRepo for MemoryDb {
+    async fn add_user(&self, user: User) -> Result<()> {
+        let mut state = self.state();
+        if state.users.contains_key(&user.user_id) {
+            let message = format!("user {} already exists", user.user_id);
+            return Err(sqlx::Error::Database(Box::new(UniqueViolation(message))).into());
+        }
+        state.users.insert(user.user_id, user);
+        Ok(())
+    }
+    async fn select_user(&self, user_id: impl Into<UserId> + Send) -> Result<Option<User>> {
+        let id: Id = user_id.into().into();
+        Ok(self.state().users.get(&id).cloned())
+    }
+    async fn select_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        Ok(self
+            .state()
+            .users
+            .values()
+            .find(|u| {
+                u.username
+                    .as_ref()
+                    .is_some_and(|n| n.eq_ignore_ascii_case(username))
+            })
+            .cloned())
+    }
+    async fn select_recent_users(&self, limit: u32) -> Result<Vec<User>> {
+        let mut users = self.state().users.values().cloned().collect::<Vec<_>>();
+        users.sort_by_key(|u| std::cmp::Reverse((u.joined_at, u.user_id)));
+        users.truncate(limit as usize);
+        Ok(users)
+    }
+    async fn select_all_users(&self) -> Result<Vec<User>> {
+        Ok(self.state().users.values().cloned().collect())
+    }
+    async fn select_users_to_notify_about_bot_update(&self) -> Result<Vec<User>> {
+        let state = self.state();
+        Ok(state
+            .users
+            .values()
+            .filter(|u| {
+                !u.bot_blocked
+                    && state
+                        .versions_notified
+                        .get(&u.user_id)
+                        .copied()
+                        .unwrap_or(0)
+                        < common::version()
+            })
+            .cloned()
+            .collect())
+    }
+    async fn save_user_lang(&self, user_id: impl Into<UserId> + Send, lang: &str) -> Result<()> {
+        self.update_user(user_id, |u| u.lang = lang.to_string());
+        Ok(())
+    }
+    async fn save_user_username(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        username: &str,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| u.username = Some(username.to_string()));
+        Ok(())
+    }
+    async fn save_user_name(&self, user_id: impl Into<UserId> + Send, name: &str) -> Result<()> {
+        self.update_user(user_id, |u| u.name = Some(name.to_string()));
+        Ok(())
+    }
+    async fn save_user_verbosity(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        verbosity: Verbosity,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| u.verbosity = verbosity);
+        Ok(())
+    }
+    async fn save_user_delivery_mode(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        delivery_mode: DeliveryMode,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| u.delivery_mode = delivery_mode);
+        Ok(())
+    }
+    async fn save_user_utc_offset(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        utc_offset: i32,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| u.utc_offset = utc_offset);
+        Ok(())
+    }
+    async fn save_user_new_sources(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        mode: NewSourcesMode,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| u.new_sources = mode);
+        Ok(())
+    }
+    async fn save_user_version_notified(&self, user_id: impl Into<UserId> + Send) -> Result<()> {
+        let id: Id = user_id.into().into();
+        let mut state = self.state();
+        if state.users.contains_key(&id) {
+            state.versions_notified.insert(id, common::version());
+        }
+        Ok(())
+    }
+    async fn select_user_version_notified(&self, user_id: impl Into<UserId> + Send) -> Result<u32> {
+        let id: Id = user_id.into().into();
+        Ok(self
+            .state()
+            .versions_notified
+            .get(&id)
+            .copied()
+            .unwrap_or_default())
+    }
+    async fn save_user_unavailable(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        blocked: bool,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| {
+            u.bot_blocked_at = match (blocked, u.bot_blocked) {
+                (false, _) => 0,
+                (true, true) => u.bot_blocked_at,
+                (true, false) => DateTime::now(),
+            };
+            u.bot_blocked = blocked;
+        });
+        Ok(())
+    }
+    async fn save_user_banned(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        banned: bool,
+    ) -> Result<()> {
+        self.update_user(user_id, |u| u.banned = banned);
+        Ok(())
+    }
+    async fn is_user_banned(&self, user_id: impl Into<UserId> + Send) -> Result<bool> {
+        let id: Id = user_id.into().into();
+        Ok(self.state().users.get(&id).is_some_and(|u| u.banned))
+    }
+    async fn delete_user(&self, user_id: impl Into<UserId> + Send) -> Result<bool> {
+        let id: Id = user_id.into().into();
+        Ok(self.state().delete_user(id))
+    }
+    async fn delete_users_blocked_before(&self, blocked_before: UnixDateTime) -> Result<Vec<Id>> {
+        let mut state = self.state();
+        let ids = state
+            .users
+            .values()
+            .filter(|u| u.bot_blocked && u.bot_blocked_at < blocked_before && !u.banned)
+            .map(|u| u.user_id)
+            .collect::<Vec<_>>();
+        for &id in &ids {
+            state.delete_user(id);
+        }
+        Ok(ids)
+    }
+}
+
+impl AppRepo for MemoryDb {
+    async fn add_or_update_app(
+        &self,
+        source_id: Id,
+        app_id: &str,
+        _name: &str,
+        last_updated_at: UnixDateTime,
+    ) -> Result<()> {
+        self.state()
+            .apps
+            .insert((source_id, app_id.to_string()), last_updated_at);
+        Ok(())
+    }
+    async fn select_users_to_notify(&self, source_id: Id, app_id: &str) -> Result<Vec<User>> {
+        let state = self.state();
+        let Some(&updated_at) = state.apps.get(&(source_id, app_id.to_string())) else {
+            return Ok(vec![]);
+        };
+        Ok(state
+            .users
+            .values()
+            .filter(|u| {
+                state.is_subscribed(u.user_id, source_id)
+                    && !u.bot_blocked
+                    && !u.banned
+                    && state
+                        .notified_at
+                        .get(&(u.user_id, source_id))
+                        .is_some_and(|&t| updated_at > t)
+            })
+            .cloned()
+            .collect())
+    }
+    async fn select_user_followed_apps(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> Result<Vec<String>> {
+        let id: Id = user_id.into().into();
+        let mut apps = self
+            .state()
+            .should_notify
+            .iter()
+            .filter(|((user_id, _, _), s)| *user_id == id && **s == ShouldNotify::Notify)
+            .map(|((_, _, app_id), _)| app_id.clone())
+            .collect::<Vec<_>>();
+        apps.sort();
+        Ok(apps)
+    }
+    async fn select_user_updates(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> Result<Vec<UserUpdate>> {
+        let id: Id = user_id.into().into();
+        let mut updates = self
+            .state()
+            .should_notify
+            .iter()
+            .filter(|((user_id, _, _), _)| *user_id == id)
+            .map(|(&(_, source_id, ref app_id), &s)| UserUpdate::new(id, source_id, app_id, s))
+            .collect::<Vec<_>>();
+        updates.sort_by(|a, b| (a.app_id(), a.source_id()).cmp(&(b.app_id(), b.source_id())));
+        Ok(updates)
+    }
+    async fn save_should_notify_user(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        source_id: Id,
+        app_id: &str,
+        should_notify: ShouldNotify,
+    ) -> Result<()> {
+        let id: Id = user_id.into().into();
+        self.state()
+            .should_notify
+            .insert((id, source_id, app_id.to_string()), should_notify);
+        Ok(())
+    }
+    async fn should_notify_user(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        source_id: Id,
+        app_id: &str,
+    ) -> Result<ShouldNotify> {
+        let id: Id = user_id.into().into();
+        Ok(self
+            .state()
+            .should_notify
+            .get(&(id, source_id, app_id.to_string()))
+            .copied()
+            .unwrap_or_default())
+    }
+    async fn save_all_users_last_notified(
+        &self,
+        source_id: Id,
+        last_notified_at: UnixDateTime,
+    ) -> Result<()> {
+        let mut state = self.state();
+        let subscribed = state
+            .users
+            .keys()
+            .copied()
+            .filter(|&id| state.is_subscribed(id, source_id))
+            .collect::<Vec<_>>();
+        for id in subscribed {
+            state.notified_at.insert((id, source_id), last_notified_at);
+            if let Some(user) = state.users.get_mut(&id) {
+                user.last_notified_at = last_notified_at;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SubscriptionRepo for MemoryDb {
+    async fn save_user_subscribed(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        source_id: Id,
+        subscribed: bool,
+    ) -> Result<()> {
+        let id: Id = user_id.into().into();
+        self.state().save_subscribed(id, source_id, subscribed);
+        Ok(())
+    }
+    async fn save_user_subscribed_all(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        subscribed: bool,
+    ) -> Result<()> {
+        let id: Id = user_id.into().into();
+        let mut state = self.state();
+        let sources = state.sources.keys().copied().collect::<Vec<_>>();
+        for source_id in sources {
+            state.save_subscribed(id, source_id, subscribed);
+        }
+        Ok(())
+    }
+    async fn select_user_subscriptions(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> Result<Vec<SourceSubscription>> {
+        let id: Id = user_id.into().into();
+        let state = self.state();
+        Ok(state
+            .sources
+            .values()
+            .map(|s| SourceSubscription {
+                source: s.clone(),
+                subscribed: state.subscriptions.get(&(id, s.source_id)).copied(),
+            })
+            .collect())
+    }
+    async fn select_users_to_notify_about_source(&self, source_id: Id) -> Result<Vec<User>> {
+        let state = self.state();
+        Ok(state
+            .users
+            .values()
+            .filter(|u| {
+                let mut subscriptions = state
+                    .subscriptions
+                    .range((u.user_id, Id::MIN)..=(u.user_id, Id::MAX));
+                !u.bot_blocked
+                    && !u.banned
+                    && subscriptions.clone().any(|(_, &subscribed)| subscribed)
+                    && !subscriptions.any(|((_, id), _)| *id == source_id)
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+impl SourceRepo for MemoryDb {
+    async fn select_sources(&self) -> Result<Vec<Source>> {
+        Ok(self.state().sources.values().cloned().collect())
+    }
+    async fn select_source(&self, source_id: Id) -> Result<Option<Source>> {
+        Ok(self.state().sources.get(&source_id).cloned())
+    }
+    async fn select_source_by_name(&self, name: &str) -> Result<Option<Source>> {
+        Ok(self
+            .state()
+            .sources
+            .values()
+            .find(|s| s.name == name)
+            .cloned())
+    }
+    async fn save_source_updated_at(
+        &self,
+        source_id: Id,
+        last_updated_at: UnixDateTime,
+    ) -> Result<()> {
+        if let Some(source) = self.state().sources.get_mut(&source_id) {
+            source.last_updated_at = last_updated_at;
+        }
+        Ok(())
+    }
+    async fn get_source_updated_at(&self, source_id: Id) -> Result<UnixDateTime> {
+        Ok(self
+            .state()
+            .sources
+            .get(&source_id)
+            .map(|s| s.last_updated_at)
+            .unwrap_or_default())
+    }
+    async fn select_sources_health(&self) -> Result<Vec<SourceHealth>> {
+        let state = self.state();
+        Ok(state
+            .sources
+            .values()
+            .map(|s| state.source_health(s))
+            .collect())
+    }
+    async fn select_source_health(&self, source_id: Id) -> Result<SourceHealth> {
+        let state = self.state();
+        let source = state
+            .sources
+            .get(&source_id)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(state.source_health(source))
+    }
+    async fn save_source_fetch_succeeded(
+        &self,
+        source_id: Id,
+        updates_found: u32,
+        time: UnixDateTime,
+    ) -> Result<()> {
+        self.state().update_health(source_id, |h| {
+            h.last_success_at = time;
+            h.updates_found += updates_found;
+            h.failing_since = 0;
+            h.consecutive_failures = 0;
+            h.alerted = false;
+        });
+        Ok(())
+    }
+    async fn save_source_fetch_failed(
+        &self,
+        source_id: Id,
+        error: &str,
+        time: UnixDateTime,
+    ) -> Result<()> {
+        self.state().update_health(source_id, |h| {
+            h.last_error = error.to_string();
+            h.last_error_at = time;
+            if h.failing_since == 0 {
+                h.failing_since = time;
+            }
+            h.consecutive_failures += 1;
+        });
+        Ok(())
+    }
+    async fn save_source_alerted(&self, source_id: Id) -> Result<()> {
+        let mut state = self.state();
+        if state.health.contains_key(&source_id) {
+            state.update_health(source_id, |h| h.alerted = true);
+        }
+        Ok(())
+    }
+}
+
+impl BroadcastRepo for MemoryDb {
+    async fn add_broadcast(&self, admin_chat_id: Id, texts: &[BroadcastText]) -> Result<Id> {
+        let mut state = self.state();
+        let broadcast_id = state.broadcasts.keys().max().map_or(1, |id| id + 1);
+        state.broadcasts.insert(
+            broadcast_id,
+            Broadcast {
+                broadcast_id,
+                admin_chat_id,
+                status: BroadcastStatus::Draft,
+                created_at: DateTime::now(),
+            },
+        );
+        let mut texts = texts.to_vec();
+        texts.sort_by(|a, b| a.lang().cmp(&b.lang()));
+        state.broadcast_texts.insert(broadcast_id, texts);
+        Ok(broadcast_id)
+    }
+    async fn select_broadcast(&self, broadcast_id: Id) -> Result<Option<Broadcast>> {
+        Ok(self.state().broadcasts.get(&broadcast_id).cloned())
+    }
+    async fn select_broadcast_texts(&self, broadcast_id: Id) -> Result<Vec<BroadcastText>> {
+        Ok(self
+            .state()
+            .broadcast_texts
+            .get(&broadcast_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+    async fn select_running_broadcasts(&self) -> Result<Vec<Broadcast>> {
+        Ok(self
+            .state()
+            .broadcasts
+            .values()
+            .filter(|b| b.status == BroadcastStatus::Running)
+            .cloned()
+            .collect())
+    }
+    async fn select_broadcast_recipients(&self, broadcast_id: Id) -> Result<Vec<User>> {
+        let state = self.state();
+        Ok(state
+            .users
+            .values()
+            .filter(|u| state.is_broadcast_recipient(broadcast_id, u))
+            .cloned()
+            .collect())
+    }
+    async fn load_broadcast_progress(&self, broadcast_id: Id) -> Result<BroadcastProgress> {
+        let state = self.state();
+        let mut progress = BroadcastProgress::default();
+        for (_, status) in state
+            .deliveries
+            .iter()
+            .filter(|((_, id), _)| *id == broadcast_id)
+        {
+            match status {
+                DeliveryStatus::Sent => progress.sent += 1,
+                DeliveryStatus::Failed => progress.failed += 1,
+                DeliveryStatus::Blocked => progress.blocked += 1,
+            }
+        }
+        progress.left = state
+            .users
+            .values()
+            .filter(|u| state.is_broadcast_recipient(broadcast_id, u))
+            .count() as u32;
+        Ok(progress)
+    }
+    async fn save_broadcast_status(
+        &self,
+        broadcast_id: Id,
+        from: BroadcastStatus,
+        to: BroadcastStatus,
+    ) -> Result<bool> {
+        Ok(match self.state().broadcasts.get_mut(&broadcast_id) {
+            Some(broadcast) if broadcast.status == from => {
+                broadcast.status = to;
+                true
+            }
+            _ => false,
+        })
+    }
+    async fn save_broadcast_delivery(
+        &self,
+        broadcast_id: Id,
+        user_id: impl Into<UserId> + Send,
+        status: DeliveryStatus,
+    ) -> Result<()> {
+        let id: Id = user_id.into().into();
+        self.state().deliveries.insert((id, broadcast_id), status);
+        Ok(())
+    }
+    async fn select_user_broadcast_deliveries(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> Result<Vec<(Id, DeliveryStatus)>> {
+        let id: Id = user_id.into().into();
+        Ok(self
+            .state()
+            .deliveries
+            .range((id, Id::MIN)..=(id, Id::MAX))
+            .map(|(&(_, broadcast_id), &status)| (broadcast_id, status))
+            .collect())
+    }
+}
+
+impl DialogueRepo for MemoryDb {
+    async fn select_dialogue(&self, chat_id: Id) -> Result<Option<Vec<u8>>> {
+        Ok(self.state().dialogues.get(&chat_id).cloned())
+    }
+    async fn save_dialogue(&self, chat_id: Id, dialogue: &[u8]) -> Result<()> {
+        self.state().dialogues.insert(chat_id, dialogue.to_vec());
+        Ok(())
+    }
+    async fn remove_dialogue(&self, chat_id: Id) -> Result<bool> {
+        Ok(self.state().dialogues.remove(&chat_id).is_some())
+    }
+}
+
+impl AdminRepo for MemoryDb {
+    async fn load_stats(&self) -> Result<Stats> {
+        let state = self.state();
+        Ok(Stats {
+            apps: state.apps.len() as u32,
+            sources: state.sources.len() as u32,
+            users: state.users.len() as u32,
+            blocked_users: state.users.values().filter(|u| u.bot_blocked).count() as u32,
+        })
+    }
+    /// Not supported, as there is no file to copy
+    async fn backup(&self, _path: &str) -> Result<()> {
+        Err(Error::Unsupported("backup"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_users() {
+        let db = MemoryDb::new();
+        db.add_source(2, "new", "");
+        for user_id in 1..=3 {
+            let user = User::builder()
+                .user_id(user_id)
+                .username(format!("User{user_id}"))
+                .build();
+            db.add_user(user).await.unwrap();
+        }
+        let user = db.select_user_by_username("user1").await.unwrap().unwrap();
+        assert_eq!(user.username(), Some("User1"));
+
+        db.save_user_subscribed(1, 1, true).await.unwrap();
+        db.save_user_subscribed(2, 1, true).await.unwrap();
+        db.save_user_subscribed(2, 2, false).await.unwrap();
+        let users = db.select_users_to_notify_about_source(2).await.unwrap();
+        assert_eq!(users.iter().map(|u| u.user_id()).collect::<Vec<_>>(), [1]);
+    }
+}
@@ -1,35 +1,60 @@
-use sqlx::{sqlite::SqliteRow, Row};
-use teloxide::utils::markdown::user_mention;
+use sqlx::{ColumnIndex, Decode, Row, Type};
+use teloxide::utils::markdown::{escape, user_mention};
 
 use common::{DateTime, UnixDateTime};
 
-use crate::{
-    types::{Id, UserId},
-    SOURCE_ID,
-};
+use crate::types::{Id, UserId};
 
-#[derive(Debug, Default, sqlx::FromRow, bon::Builder)]
+#[derive(Debug, Default, Clone, sqlx::FromRow, bon::Builder)]
 pub struct User {
     /// User ID
-    user_id: Id,
+    pub(crate) user_id: Id,
 
     /// Username
-    username: Option<String>,
+    pub(crate) username: Option<String>,
 
     /// First name + last name
-    name: Option<String>,
+    pub(crate) name: Option<String>,
 
     /// Language, selected by user
     #[builder(default = i18n::DEFAULT_USER_LANG.to_string())]
-    lang: String,
+    pub(crate) lang: String,
 
     /// When user was last notified
     #[builder(default = DateTime::now())]
-    last_notified_at: UnixDateTime,
+    pub(crate) last_notified_at: UnixDateTime,
 
     /// Is bot blocked by user
     #[builder(default)]
-    bot_blocked: bool,
+    pub(crate) bot_blocked: bool,
+
+    /// When user blocked bot, 0 if not blocked
+    #[builder(default)]
+    pub(crate) bot_blocked_at: UnixDateTime,
+
+    /// How detailed notifications should be
+    #[builder(default)]
+    pub(crate) verbosity: Verbosity,
+
+    /// How notifications should be delivered
+    #[builder(default)]
+    pub(crate) delivery_mode: DeliveryMode,
+
+    /// User's timezone, minutes from UTC
+    #[builder(default)]
+    pub(crate) utc_offset: i32,
+
+    /// What to do when new source is added
+    #[builder(default)]
+    pub(crate) new_sources: NewSourcesMode,
+
+    /// Is user banned by admin
+    #[builder(default)]
+    pub(crate) banned: bool,
+
+    /// When user started bot, 0 if unknown
+    #[builder(default = DateTime::now())]
+    pub(crate) joined_at: UnixDateTime,
 }
 
 impl User {
@@ -52,32 +77,163 @@ impl User {
     pub fn bot_blocked(&self) -> bool {
         self.bot_blocked
     }
-    /// Display user name. Can contain link to user, which is only works
-    /// inside inline links, so message should be set to markdown
+    pub fn bot_blocked_at(&self) -> UnixDateTime {
+        self.bot_blocked_at
+    }
+    pub fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+    pub fn delivery_mode(&self) -> DeliveryMode {
+        self.delivery_mode
+    }
+    pub fn utc_offset(&self) -> i32 {
+        self.utc_offset
+    }
+    pub fn new_sources(&self) -> NewSourcesMode {
+        self.new_sources
+    }
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref().filter(|u| !u.is_empty())
+    }
+    /// First name + last name
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref().filter(|n| !n.is_empty())
+    }
+    pub fn banned(&self) -> bool {
+        self.banned
+    }
+    pub fn joined_at(&self) -> UnixDateTime {
+        self.joined_at
+    }
+    /// Display user name, escaped for MarkdownV2. Can contain link to user,
+    /// which is only works inside inline links, so message should be set to
+    /// markdown
     pub fn display(&self) -> String {
-        match (&self.username, &self.name) {
-            (Some(username), Some(name)) => format!("@{username} ({name})"),
+        let username = self.username.as_deref().map(escape);
+        let name = self.name.as_deref().map(escape);
+        match (username, name) {
+            (Some(username), Some(name)) => format!("@{username} \\({name}\\)"),
             (Some(username), None) => format!("@{username}"),
-            (None, Some(name)) => user_mention(self.tg_user_id().into(), name),
+            (None, Some(name)) => user_mention(self.tg_user_id().into(), &name),
             (None, None) => user_mention(self.tg_user_id().into(), &self.user_id.to_string()),
         }
     }
 }
 
-#[derive(Debug, Default)]
+/// How detailed notifications about updates should be
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Verbosity {
+    /// Only one line with app name
+    Minimal,
+    /// App name and one link
+    #[default]
+    Normal,
+    /// Everything known about update, with all links
+    Detailed,
+}
+
+impl Verbosity {
+    pub const ALL: [Self; 3] = [Self::Minimal, Self::Normal, Self::Detailed];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minimal => "minimal",
+            Self::Normal => "normal",
+            Self::Detailed => "detailed",
+        }
+    }
+}
+
+impl TryFrom<&str> for Verbosity {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|v| v.as_str() == value)
+            .ok_or(())
+    }
+}
+
+/// How notifications about updates should be delivered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum DeliveryMode {
+    /// Regular message with sound
+    #[default]
+    Normal,
+    /// Message without sound
+    Silent,
+}
+
+impl DeliveryMode {
+    pub const ALL: [Self; 2] = [Self::Normal, Self::Silent];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Silent => "silent",
+        }
+    }
+}
+
+impl TryFrom<&str> for DeliveryMode {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|v| v.as_str() == value)
+            .ok_or(())
+    }
+}
+
+/// What to do with user when new source is added
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum NewSourcesMode {
+    /// Ask user, whether to subscribe
+    #[default]
+    Ask,
+    /// Subscribe automatically
+    Subscribe,
+}
+
+impl NewSourcesMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ask => "ask",
+            Self::Subscribe => "subscribe",
+        }
+    }
+}
+
+impl TryFrom<&str> for NewSourcesMode {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        [Self::Ask, Self::Subscribe]
+            .into_iter()
+            .find(|v| v.as_str() == value)
+            .ok_or(())
+    }
+}
+
+#[derive(Debug, Default, sqlx::FromRow)]
 pub struct UserUpdate {
     user_id: Id,
-    #[allow(unused)]
     source_id: Id,
     app_id: String,
+    #[sqlx(flatten)]
     should_notify: ShouldNotify,
 }
 
 impl UserUpdate {
-    pub fn new(user_id: Id, app_id: &str, should_notify: ShouldNotify) -> Self {
+    pub fn new(user_id: Id, source_id: Id, app_id: &str, should_notify: ShouldNotify) -> Self {
         Self {
             user_id,
-            source_id: SOURCE_ID,
+            source_id,
             app_id: app_id.to_string(),
             should_notify,
         }
@@ -85,6 +241,9 @@ impl UserUpdate {
     pub fn user_id(&self) -> Id {
         self.user_id
     }
+    pub fn source_id(&self) -> Id {
+        self.source_id
+    }
     pub fn app_id(&self) -> &str {
         self.app_id.as_str()
     }
@@ -111,13 +270,18 @@ impl ShouldNotify {
     }
 }
 
-impl sqlx::FromRow<'_, SqliteRow> for ShouldNotify {
-    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
-        let res: u8 = row.try_get("should_notify")?;
-        if res == 0 {
-            Ok(Self::Ignore)
-        } else {
+impl<'r, R> sqlx::FromRow<'r, R> for ShouldNotify
+where
+    R: Row,
+    bool: Decode<'r, R::Database> + Type<R::Database>,
+    &'r str: ColumnIndex<R>,
+{
+    fn from_row(row: &'r R) -> Result<Self, sqlx::Error> {
+        let res: bool = row.try_get("should_notify")?;
+        if res {
             Ok(Self::Notify)
+        } else {
+            Ok(Self::Ignore)
         }
     }
 }
@@ -125,22 +289,24 @@ impl sqlx::FromRow<'_, SqliteRow> for ShouldNotify {
 #[derive(Debug, Default)]
 pub struct UserSubscribe {
     user_id: Id,
-    #[allow(unused)]
     source_id: Id,
     subscribed: bool,
 }
 
 impl UserSubscribe {
-    pub fn new(user_id: UserId, subscribed: bool) -> Self {
+    pub fn new(user_id: UserId, source_id: Id, subscribed: bool) -> Self {
         Self {
             user_id: user_id.into(),
-            source_id: SOURCE_ID,
+            source_id,
             subscribed,
         }
     }
     pub fn user_id(&self) -> Id {
         self.user_id
     }
+    pub fn source_id(&self) -> Id {
+        self.source_id
+    }
     pub fn subscribed(&self) -> bool {
         self.subscribed
     }
@@ -177,20 +343,193 @@ impl App {
     }
 }
 
-#[allow(unused)]
-#[derive(Debug, sqlx::FromRow)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct Source {
-    source_id: Id,
-    name: String,
-    last_updated_at: UnixDateTime,
+    pub(crate) source_id: Id,
+    /// Name in form `{kind}@{id}`, e.g. `tg@channel`
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) last_updated_at: UnixDateTime,
 }
 
 impl Source {
+    pub fn source_id(&self) -> Id {
+        self.source_id
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Name to show to user: `@channel` for telegram channels, otherwise
+    /// name as is
+    pub fn display_name(&self) -> String {
+        match self.name.strip_prefix("tg@") {
+            Some(channel) => format!("@{channel}"),
+            None => self.name.clone(),
+        }
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
     pub fn last_updated_at(&self) -> UnixDateTime {
         self.last_updated_at
     }
 }
 
+/// Source with user's subscription to it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SourceSubscription {
+    #[sqlx(flatten)]
+    pub(crate) source: Source,
+    /// `None` if user never subscribed or unsubscribed
+    pub(crate) subscribed: Option<bool>,
+}
+
+impl SourceSubscription {
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+    pub fn subscribed(&self) -> bool {
+        self.subscribed.unwrap_or_default()
+    }
+}
+
+/// Source with results of fetching it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SourceHealth {
+    #[sqlx(flatten)]
+    pub(crate) source: Source,
+    /// 0 if never fetched successfully
+    pub(crate) last_success_at: UnixDateTime,
+    pub(crate) last_error: String,
+    pub(crate) last_error_at: UnixDateTime,
+    /// First failure in a row, 0 if not failing
+    pub(crate) failing_since: UnixDateTime,
+    #[sqlx(try_from = "i64")]
+    pub(crate) consecutive_failures: u32,
+    /// Total count of found new updates
+    #[sqlx(try_from = "i64")]
+    pub(crate) updates_found: u32,
+    /// Admin was alerted about failure
+    pub(crate) alerted: bool,
+}
+
+impl SourceHealth {
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+    pub fn last_success_at(&self) -> UnixDateTime {
+        self.last_success_at
+    }
+    /// Last error with its time
+    pub fn last_error(&self) -> Option<(&str, UnixDateTime)> {
+        (!self.last_error.is_empty()).then_some((self.last_error.as_str(), self.last_error_at))
+    }
+    pub fn failing_since(&self) -> Option<UnixDateTime> {
+        (self.failing_since > 0).then_some(self.failing_since)
+    }
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+    pub fn updates_found(&self) -> u32 {
+        self.updates_found
+    }
+    pub fn alerted(&self) -> bool {
+        self.alerted
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Broadcast {
+    pub(crate) broadcast_id: Id,
+    /// Admin, who created broadcast
+    pub(crate) admin_chat_id: Id,
+    pub(crate) status: BroadcastStatus,
+    pub(crate) created_at: UnixDateTime,
+}
+
+impl Broadcast {
+    pub fn broadcast_id(&self) -> Id {
+        self.broadcast_id
+    }
+    pub fn admin_chat_id(&self) -> Id {
+        self.admin_chat_id
+    }
+    pub fn status(&self) -> BroadcastStatus {
+        self.status
+    }
+    pub fn created_at(&self) -> UnixDateTime {
+        self.created_at
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum BroadcastStatus {
+    /// Waiting for confirmation from admin
+    Draft,
+    /// Confirmed, sending to users
+    Running,
+    /// Sent to all users
+    Done,
+    Cancelled,
+}
+
+/// Text of broadcast for one language
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct BroadcastText {
+    /// Empty for default text
+    lang: String,
+    text: String,
+}
+
+impl BroadcastText {
+    pub fn new(lang: &str, text: &str) -> Self {
+        Self {
+            lang: lang.to_string(),
+            text: text.to_string(),
+        }
+    }
+    /// Text for all languages, without own variant
+    pub fn default_text(text: &str) -> Self {
+        Self::new("", text)
+    }
+    pub fn lang(&self) -> Option<&str> {
+        (!self.lang.is_empty()).then_some(self.lang.as_str())
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Result of sending broadcast to one user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Sent,
+    Failed,
+    /// Bot blocked by user or user deactivated
+    Blocked,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+            Self::Blocked => "blocked",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BroadcastProgress {
+    pub sent: u32,
+    pub failed: u32,
+    pub blocked: u32,
+    /// Users, to which broadcast is not yet sent
+    pub left: u32,
+}
+
 #[derive(Debug)]
 pub struct Stats {
     pub apps: u32,
@@ -198,3 +537,41 @@ pub struct Stats {
     pub users: u32,
     pub blocked_users: u32,
 }
+
+/// Count of users by state. Banned users are not counted as blocked
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UserStates {
+    pub active: u32,
+    pub blocked: u32,
+    pub banned: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_display() {
+        let user = |username: Option<&str>, name: Option<&str>| {
+            User::builder()
+                .user_id(1)
+                .maybe_username(username.map(str::to_string))
+                .maybe_name(name.map(str::to_string))
+                .build()
+        };
+        for (i, (user, expected)) in [
+            (
+                user(Some("some_user"), Some("Jo-Jo (J.)")),
+                r"@some\_user \(Jo\-Jo \(J\.\)\)",
+            ),
+            (user(Some("some_user"), None), r"@some\_user"),
+            (user(None, Some("Jo_Jo")), r"[Jo\_Jo](tg://user?id=1)"),
+            (user(None, None), "[1](tg://user?id=1)"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_eq!(user.display(), expected, "test table[{i}]");
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Operations of storage, which handlers depend on. Implemented by [`DB`]
+//! and by `MemoryDb` for tests, with `memory` feature
+//!
+//! [`DB`]: crate::DB
+
+use std::future::Future;
+
+use common::UnixDateTime;
+
+use crate::{
+    models::{
+        Broadcast, BroadcastProgress, BroadcastStatus, BroadcastText, DeliveryMode, DeliveryStatus,
+        NewSourcesMode, ShouldNotify, Source, SourceHealth, SourceSubscription, Stats, User,
+        UserUpdate, Verbosity,
+    },
+    types::{Id, UserId},
+    Result,
+};
+
+pub trait UserRepo {
+    fn add_user(&self, user: User) -> impl Future<Output = Result<()>> + Send;
+    fn select_user(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<Option<User>>> + Send;
+    /// Select user by username, without `@`
+    fn select_user_by_username(
+        &self,
+        username: &str,
+    ) -> impl Future<Output = Result<Option<User>>> + Send;
+    /// Select last joined users
+    fn select_recent_users(&self, limit: u32) -> impl Future<Output = Result<Vec<User>>> + Send;
+    fn select_all_users(&self) -> impl Future<Output = Result<Vec<User>>> + Send;
+    /// Select users, not yet notified about bot update
+    fn select_users_to_notify_about_bot_update(
+        &self,
+    ) -> impl Future<Output = Result<Vec<User>>> + Send;
+    fn save_user_lang(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        lang: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_username(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        username: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_name(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        name: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_verbosity(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        verbosity: Verbosity,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_delivery_mode(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        delivery_mode: DeliveryMode,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Save user's timezone, `utc_offset` is minutes from UTC
+    fn save_user_utc_offset(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        utc_offset: i32,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_new_sources(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        mode: NewSourcesMode,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_version_notified(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Last version of bot, about which user was notified, 0 if none
+    fn select_user_version_notified(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<u32>> + Send;
+    /// Save that chat with user is not available
+    fn save_user_unavailable(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        blocked: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_user_banned(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        banned: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn is_user_banned(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    /// Delete user with all their subscriptions and choices. Returns
+    /// `false` if there was no user
+    fn delete_user(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    /// Delete users, who blocked bot before `blocked_before`. Banned users
+    /// are kept, so ban is not lifted. Returns ids of deleted users
+    fn delete_users_blocked_before(
+        &self,
+        blocked_before: UnixDateTime,
+    ) -> impl Future<Output = Result<Vec<Id>>> + Send;
+}
+
+pub trait AppRepo {
+    /// Add new app, if there is already exists app with
+    /// (`app_id`, `source_id`), update `last_updated_at`
+    fn add_or_update_app(
+        &self,
+        source_id: Id,
+        app_id: &str,
+        name: &str,
+        last_updated_at: UnixDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Select users, subscribed to source `source_id`, which were not
+    /// notified since last update of app. Blocked and banned users are
+    /// skipped
+    fn select_users_to_notify(
+        &self,
+        source_id: Id,
+        app_id: &str,
+    ) -> impl Future<Output = Result<Vec<User>>> + Send;
+    /// Select apps, for which user enabled notifications
+    fn select_user_followed_apps(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send;
+    /// Select all choices of user about apps, ordered by app
+    fn select_user_updates(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<Vec<UserUpdate>>> + Send;
+    fn save_should_notify_user(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        source_id: Id,
+        app_id: &str,
+        should_notify: ShouldNotify,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn should_notify_user(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        source_id: Id,
+        app_id: &str,
+    ) -> impl Future<Output = Result<ShouldNotify>> + Send;
+    /// Set `last_notified_at` for all users, subscribed to source `source_id`
+    fn save_all_users_last_notified(
+        &self,
+        source_id: Id,
+        last_notified_at: UnixDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+pub trait SubscriptionRepo {
+    fn save_user_subscribed(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        source_id: Id,
+        subscribed: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Subscribe or unsubscribe user from all sources
+    fn save_user_subscribed_all(
+        &self,
+        user_id: impl Into<UserId> + Send,
+        subscribed: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Select all sources with user's subscriptions to them
+    fn select_user_subscriptions(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<Vec<SourceSubscription>>> + Send;
+    /// Select users, who subscribed to some sources, but not yet decided
+    /// about source `source_id`. Blocked and banned users are skipped
+    fn select_users_to_notify_about_source(
+        &self,
+        source_id: Id,
+    ) -> impl Future<Output = Result<Vec<User>>> + Send;
+}
+
+pub trait SourceRepo {
+    fn select_sources(&self) -> impl Future<Output = Result<Vec<Source>>> + Send;
+    fn select_source(&self, source_id: Id) -> impl Future<Output = Result<Option<Source>>> + Send;
+    fn select_source_by_name(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<Source>>> + Send;
+    fn save_source_updated_at(
+        &self,
+        source_id: Id,
+        last_updated_at: UnixDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn get_source_updated_at(
+        &self,
+        source_id: Id,
+    ) -> impl Future<Output = Result<UnixDateTime>> + Send;
+    /// Health of all sources, including never fetched
+    fn select_sources_health(&self) -> impl Future<Output = Result<Vec<SourceHealth>>> + Send;
+    fn select_source_health(
+        &self,
+        source_id: Id,
+    ) -> impl Future<Output = Result<SourceHealth>> + Send;
+    /// Save successful fetch. `updates_found` is count of new updates, it's
+    /// added to total
+    fn save_source_fetch_succeeded(
+        &self,
+        source_id: Id,
+        updates_found: u32,
+        time: UnixDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn save_source_fetch_failed(
+        &self,
+        source_id: Id,
+        error: &str,
+        time: UnixDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Save that admin was alerted about source failure
+    fn save_source_alerted(&self, source_id: Id) -> impl Future<Output = Result<()>> + Send;
+}
+
+pub trait BroadcastRepo {
+    /// Save new broadcast as draft, returns its id
+    fn add_broadcast(
+        &self,
+        admin_chat_id: Id,
+        texts: &[BroadcastText],
+    ) -> impl Future<Output = Result<Id>> + Send;
+    fn select_broadcast(
+        &self,
+        broadcast_id: Id,
+    ) -> impl Future<Output = Result<Option<Broadcast>>> + Send;
+    fn select_broadcast_texts(
+        &self,
+        broadcast_id: Id,
+    ) -> impl Future<Output = Result<Vec<BroadcastText>>> + Send;
+    /// Broadcasts, which were confirmed, but not finished
+    fn select_running_broadcasts(&self) -> impl Future<Output = Result<Vec<Broadcast>>> + Send;
+    /// Users, to which broadcast is not yet sent. Blocked and banned users
+    /// are skipped
+    fn select_broadcast_recipients(
+        &self,
+        broadcast_id: Id,
+    ) -> impl Future<Output = Result<Vec<User>>> + Send;
+    fn load_broadcast_progress(
+        &self,
+        broadcast_id: Id,
+    ) -> impl Future<Output = Result<BroadcastProgress>> + Send;
+    /// Change status of broadcast, only if it currently has status `from`.
+    /// Returns `false` if status was not changed
+    fn save_broadcast_status(
+        &self,
+        broadcast_id: Id,
+        from: BroadcastStatus,
+        to: BroadcastStatus,
+    ) -> impl Future<Output = Result<bool>> + Send;
+    fn save_broadcast_delivery(
+        &self,
+        broadcast_id: Id,
+        user_id: impl Into<UserId> + Send,
+        status: DeliveryStatus,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Deliveries of broadcasts to user, by `broadcast_id`
+    fn select_user_broadcast_deliveries(
+        &self,
+        user_id: impl Into<UserId> + Send,
+    ) -> impl Future<Output = Result<Vec<(Id, DeliveryStatus)>>> + Send;
+}
+
+pub trait DialogueRepo {
+    /// Serialized state of dialogue with chat
+    fn select_dialogue(&self, chat_id: Id) -> impl Future<Output = Result<Option<Vec<u8>>>> + Send;
+    fn save_dialogue(
+        &self,
+        chat_id: Id,
+        dialogue: &[u8],
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Remove dialogue with chat. Returns `false` if there was no dialogue
+    fn remove_dialogue(&self, chat_id: Id) -> impl Future<Output = Result<bool>> + Send;
+}
+
+pub trait AdminRepo {
+    fn load_stats(&self) -> impl Future<Output = Result<Stats>> + Send;
+    /// Write consistent copy of storage to new file at `path`
+    fn backup(&self, path: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// All operations, which handlers depend on
+pub trait Repository:
+    UserRepo
+    + AppRepo
+    + SubscriptionRepo
+    + SourceRepo
+    + BroadcastRepo
+    + DialogueRepo
+    + AdminRepo
+    + Clone
+    + Send
+    + Sync
+    + 'static
+{
+}
+
+impl<T> Repository for T where
+    T: UserRepo
+        + AppRepo
+        + SubscriptionRepo
+        + SourceRepo
+        + BroadcastRepo
+        + DialogueRepo
+        + AdminRepo
+        + Clone
+        + Send
+        + Sync
+        + 'static
+{
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use camino::Utf8PathBuf as PathBuf;
 use fluent::FluentResource;
 use fluent_syntax::ast::{Entry, Expression, InlineExpression, Message, Pattern, PatternElement};
@@ -27,8 +29,7 @@ pub(crate) fn extract_messages(
                 .join("\n")
         })?;
 
-        let mut messages = vec![];
-
+        let mut placeables = HashMap::new();
         for e in resource.entries() {
             if let Entry::Message(Message {
                 id,
@@ -36,16 +37,35 @@ pub(crate) fn extract_messages(
                 ..
             }) = e
             {
-                let mut attrs = vec![];
+                let mut args = vec![];
                 for e in elements {
-                    if let PatternElement::Placeable {
-                        expression:
-                            Expression::Inline(InlineExpression::VariableReference { id, .. }),
-                    } = e
-                    {
-                        attrs.push(id.name.to_owned());
+                    match e {
+                        PatternElement::Placeable {
+                            expression:
+                                Expression::Inline(InlineExpression::VariableReference { id }),
+                        } => args.push(Placeable::Variable(id.name)),
+                        PatternElement::Placeable {
+                            expression:
+                                Expression::Inline(InlineExpression::MessageReference {
+                                    id,
+                                    attribute: None,
+                                }),
+                        } => args.push(Placeable::Message(id.name)),
+                        _ => {}
                     }
                 }
+                placeables.insert(id.name, args);
+            }
+        }
+
+        let mut messages = vec![];
+        for e in resource.entries() {
+            if let Entry::Message(Message {
+                id, value: Some(_), ..
+            }) = e
+            {
+                let mut attrs = vec![];
+                collect_attrs(id.name, &placeables, &mut vec![], &mut attrs);
                 messages.push(MessageInfo {
                     id: id.name.to_owned(),
                     attrs,
@@ -65,6 +85,41 @@ pub(crate) fn extract_messages(
     Ok(res)
 }
 
+/// Variable or message, referenced in message
+enum Placeable<'s> {
+    Variable(&'s str),
+    Message(&'s str),
+}
+
+/// Collect variables of message `id` in order of appearance. Referenced
+/// messages are formatted with arguments of referencing one, so their
+/// variables are collected too
+fn collect_attrs<'s>(
+    id: &'s str,
+    placeables: &HashMap<&'s str, Vec<Placeable<'s>>>,
+    visiting: &mut Vec<&'s str>,
+    attrs: &mut Vec<String>,
+) {
+    if visiting.contains(&id) {
+        return;
+    }
+    let Some(args) = placeables.get(id) else {
+        return;
+    };
+    visiting.push(id);
+    for arg in args {
+        match *arg {
+            Placeable::Variable(name) => {
+                if !attrs.iter().any(|a| a == name) {
+                    attrs.push(name.to_owned());
+                }
+            }
+            Placeable::Message(id) => collect_attrs(id, placeables, visiting, attrs),
+        }
+    }
+    visiting.pop();
+}
+
 fn extract_lang(path: PathBuf) -> Result<LanguageIdentifier, String> {
     if !matches!(path.extension(), Some("ftl")) {
         return Err(format!(
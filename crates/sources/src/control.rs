@@ -0,0 +1,134 @@
+use tokio::sync::{mpsc::Sender, oneshot};
+
+use common::Heartbeat;
+
+use crate::Update;
+
+/// Result of fetch, requested from [`SourcesControl`]: count of found updates
+pub type FetchResult = Result<usize, String>;
+
+/// Result of preview, requested from [`SourcesControl`]: updates, which would
+/// be sent to users
+pub type PreviewResult = Result<Vec<Update>, String>;
+
+/// Command to update loop of source
+#[derive(Debug)]
+pub(crate) enum SourceCommand {
+    /// Fetch immediately, without waiting for timeout
+    Fetch { reply: oneshot::Sender<FetchResult> },
+    /// Fetch and parse, but don't send updates further and don't reset timer
+    Preview {
+        reply: oneshot::Sender<PreviewResult>,
+    },
+}
+
+/// Handle to send commands to update loops of running sources
+#[derive(Debug, Clone, Default)]
+pub struct SourcesControl {
+    sources: Vec<SourceHandle>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceHandle {
+    name: &'static str,
+    tx: Sender<SourceCommand>,
+    heartbeat: Heartbeat,
+}
+
+impl SourcesControl {
+    pub(crate) fn add(
+        &mut self,
+        name: &'static str,
+        tx: Sender<SourceCommand>,
+        heartbeat: Heartbeat,
+    ) {
+        self.sources.push(SourceHandle {
+            name,
+            tx,
+            heartbeat,
+        });
+    }
+    /// Names of running sources
+    pub fn names(&self) -> Vec<&'static str> {
+        self.sources.iter().map(|s| s.name).collect()
+    }
+    /// Names of sources, which update loops are stalled
+    pub fn stalled(&self) -> Vec<&'static str> {
+        self.sources
+            .iter()
+            .filter(|s| s.heartbeat.is_stalled())
+            .map(|s| s.name)
+            .collect()
+    }
+    /// Fetch sources, matching `query` (see [`matches_source`]), immediately.
+    /// Returns result for each matched source
+    pub async fn fetch(&self, query: &str) -> Vec<(&'static str, FetchResult)> {
+        self.send(query, |reply| SourceCommand::Fetch { reply })
+            .await
+    }
+    /// Dry-run sources, matching `query` (see [`matches_source`]): updates are
+    /// only returned, without saving or notifying users
+    pub async fn preview(&self, query: &str) -> Vec<(&'static str, PreviewResult)> {
+        self.send(query, |reply| SourceCommand::Preview { reply })
+            .await
+    }
+    async fn send<T, F>(&self, query: &str, command: F) -> Vec<(&'static str, Result<T, String>)>
+    where
+        F: Fn(oneshot::Sender<Result<T, String>>) -> SourceCommand,
+    {
+        let mut results = vec![];
+        for SourceHandle { name, tx, .. } in &self.sources {
+            if !matches_source(name, query) {
+                continue;
+            }
+            let (reply, rx) = oneshot::channel();
+            let res = match tx.send(command(reply)).await {
+                Ok(()) => rx
+                    .await
+                    .unwrap_or_else(|_| Err("source stopped before replying".to_string())),
+                Err(_) => Err("source is stopped".to_string()),
+            };
+            results.push((*name, res));
+        }
+        results
+    }
+}
+
+/// Check if source name matches query. Query can be full name (`tg@channel`),
+/// or name without kind (`channel` or `@channel`). Empty query matches all
+/// sources
+pub(crate) fn matches_source(name: &str, query: &str) -> bool {
+    let query = query.trim();
+    if query.is_empty() || query.eq_ignore_ascii_case(name) {
+        return true;
+    }
+    let short = name.split_once('@').map(|(_, s)| s).unwrap_or(name);
+    query
+        .strip_prefix('@')
+        .unwrap_or(query)
+        .eq_ignore_ascii_case(short)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_source() {
+        let table = [
+            ("", true),
+            ("tg@channel", true),
+            ("channel", true),
+            ("@Channel", true),
+            ("chan", false),
+            ("tg@other", false),
+        ];
+        for (i, (query, expected)) in table.into_iter().enumerate() {
+            assert_eq!(
+                matches_source("tg@channel", query),
+                expected,
+                "test table[{i}]"
+            );
+        }
+    }
+}
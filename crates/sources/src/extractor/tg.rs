@@ -7,18 +7,23 @@ use serde::{Deserialize, Serialize};
 
 use common::{LogError, UnixDateTime};
 
+use crate::RetryAfter;
+
 const API_URL: &str = "https://tg.i-c-a.su/json/";
 const API_LIMIT_MSGS: u32 = 10;
 const MAX_RETRIES: u32 = 5;
 
-/// Returns messages in order from new to old.
+/// Returns messages in order from new to old. When retries on FLOOD_WAIT are
+/// exhausted, returns [`RetryAfter`] error with last requested wait
 pub(crate) async fn fetch_public_channel(name: &str) -> Result<Vec<Message>> {
     log::debug!("fetching updates for {name}");
 
     // retry on FLOOD_WAIT
+    let mut last_wait = Duration::ZERO;
     for _ in 0..MAX_RETRIES {
         match fetch_public_channel_impl(name).await {
             Err(FetchError::FloodWait(wait)) => {
+                last_wait = wait;
                 tokio::time::sleep(wait).await;
             }
             res => {
@@ -28,7 +33,7 @@ pub(crate) async fn fetch_public_channel(name: &str) -> Result<Vec<Message>> {
         }
     }
     log::error!("failed to fetch telegram/{name} in {MAX_RETRIES} retries");
-    Err(FetchError::FloodWaitFailed.into())
+    Err(RetryAfter(last_wait).into())
 }
 
 async fn fetch_public_channel_impl(name: &str) -> Result<Vec<Message>, FetchError> {
@@ -73,17 +78,15 @@ struct Response {
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
-enum ResponseError {
+pub(crate) enum ResponseError {
     String(String),
     Any(serde_json::Value),
 }
 
 #[derive(Debug, thiserror::Error)]
-enum FetchError {
+pub(crate) enum FetchError {
     #[error("flood wait: {0:?}")]
     FloodWait(Duration),
-    #[error("retry on flood wait failed in {MAX_RETRIES} retries")]
-    FloodWaitFailed,
     #[error("got errors: {0:?}")]
     Arbitrary(Vec<ResponseError>),
     #[error("got no messages, raw content: {full:?}")]
@@ -94,6 +97,18 @@ enum FetchError {
     JsonParse(#[from] serde_json::Error),
 }
 
+impl FetchError {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::FloodWait(_) => "flood_wait",
+            Self::Arbitrary(_) => "telegram",
+            Self::Empty { .. } => "empty",
+            Self::Reqwest(_) => "network",
+            Self::JsonParse(_) => "json",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Default))]
 pub(crate) struct Message {
@@ -2,70 +2,157 @@
 use std::time::Duration;
 
 use async_trait::async_trait;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{Receiver, Sender};
 
+use common::{Heartbeat, UnixDateTime};
+
+use control::SourceCommand;
+use scheduler::Scheduler;
+
+mod control;
 mod extractor;
+mod scheduler;
 mod sources;
-mod timer;
+mod status;
 mod update;
 
-pub use sources::spawn_sources_update_jobs;
+pub use control::{FetchResult, PreviewResult, SourcesControl};
+pub use scheduler::{Clock, ScheduleConfig, SystemClock};
+pub use sources::{preview_sources, spawn_sources_update_jobs, SourceConfig, SOURCE_NAMES};
+pub use status::FetchStatus;
 pub use update::*;
 
-pub(crate) const SOURCE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
-
 /// Source that can be fetched for update
-#[async_trait]
 pub trait UpdateSource {
     type InitError;
 
-    /// Create source with default timeout
-    fn new() -> Result<Self, Self::InitError>
-    where
-        Self: Sized,
-    {
-        Self::with_timeout(SOURCE_TIMEOUT)
-    }
+    /// Name of source, same as in database, e.g. `tg@channel`
+    const NAME: &'static str;
 
-    /// Create source with specific timeout
-    fn with_timeout(timeout: Duration) -> Result<Self, Self::InitError>
+    fn new() -> Result<Self, Self::InitError>
     where
-        Self: std::marker::Sized;
-
-    /// How long should wait until next fetch, None if should not wait
-    fn wait_remains(&self) -> Option<Duration>;
-
-    /// Wait until timeout end
-    async fn sleep(&self) {
-        if let Some(d) = self.wait_remains() {
-            tokio::time::sleep(d).await;
-        }
-    }
-
-    fn reset_timer(&self);
+        Self: Sized;
 }
 
 #[async_trait]
 pub trait UpdateSourceList: UpdateSource {
     /// Fetch updates
-    async fn get_updates(&self) -> UpdatesList;
-
-    /// Sleep if timeout isn't end, then fetch updates
-    async fn get_updates_after_sleep(&self) -> UpdatesList {
-        self.sleep().await;
-        let res = self.get_updates().await;
-        self.reset_timer();
-        res
+    async fn get_updates(&self) -> anyhow::Result<UpdatesList>;
+}
+
+/// Kind of fetch error for metrics
+fn fetch_error_kind(e: &anyhow::Error) -> &'static str {
+    if e.is::<RetryAfter>() {
+        "flood_wait"
+    } else if let Some(e) = e.downcast_ref::<extractor::tg::FetchError>() {
+        e.kind()
+    } else {
+        "other"
     }
 }
 
-/// Start update loop for UpdateSourceList
-pub async fn start_list_update_loop<S>(source: S, tx: Sender<UpdatesList>)
-where
+/// Update loop is considered stalled, if fetch is not finished in this time
+/// after it's scheduled. Fetch can take long, when it waits on FLOOD_WAIT
+pub(crate) const FETCH_STALL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Error, after which source asked not to fetch it for some time
+#[derive(Debug, thiserror::Error)]
+#[error("rate limited, should retry after {0:?}")]
+pub(crate) struct RetryAfter(pub(crate) Duration);
+
+/// Start update loop for UpdateSourceList. Commands from `control` are
+/// handled while waiting for next fetch. Fetched posts overlap with previous
+/// fetch, so only updates newer than already seen are counted as found.
+/// `last_update` is time of newest update, seen before start, so updates
+/// are not counted again after restart
+pub(crate) async fn start_list_update_loop<S>(
+    source: S,
+    mut last_update: Option<UnixDateTime>,
+    mut scheduler: Scheduler,
+    tx: Sender<UpdatesList>,
+    status_tx: Sender<FetchStatus>,
+    control: Receiver<SourceCommand>,
+    heartbeat: Heartbeat,
+) where
     S: UpdateSourceList + Send + Sync,
 {
+    let mut control = Some(control);
     loop {
-        let updates = source.get_updates_after_sleep().await;
+        heartbeat.beat(scheduler.wait_remains() + FETCH_STALL_TIMEOUT);
+        let sleep = tokio::time::sleep(scheduler.wait_remains());
+        let reply = match control.as_mut() {
+            Some(rx) => tokio::select! {
+                _ = sleep => None,
+                cmd = rx.recv() => match cmd {
+                    Some(SourceCommand::Fetch { reply }) => {
+                        log::debug!("got command to fetch {}", S::NAME);
+                        Some(reply)
+                    }
+                    Some(SourceCommand::Preview { reply }) => {
+                        log::debug!("got command to preview {}", S::NAME);
+                        let res = source
+                            .get_updates()
+                            .await
+                            .map(|u| u.updates)
+                            .map_err(|e| e.to_string());
+                        if reply.send(res).is_err() {
+                            log::error!("failed to reply to preview command");
+                        }
+                        continue;
+                    }
+                    None => {
+                        // all control handles are dropped
+                        control = None;
+                        continue;
+                    }
+                },
+            },
+            None => {
+                sleep.await;
+                None
+            }
+        };
+
+        let res = source.get_updates().await;
+        let new_updates = match &res {
+            Ok(updates) => {
+                let new_updates = updates.count_newer(last_update);
+                last_update = last_update.max(Some(updates.last_update));
+                metrics::counter!("source_updates_fetched_total", "source" => S::NAME)
+                    .increment(new_updates as u64);
+                scheduler.on_success(new_updates);
+                new_updates
+            }
+            Err(e) => {
+                metrics::counter!(
+                    "source_fetch_errors_total",
+                    "source" => S::NAME,
+                    "kind" => fetch_error_kind(e),
+                )
+                .increment(1);
+                scheduler.on_error(e.downcast_ref::<RetryAfter>().map(|r| r.0));
+                0
+            }
+        };
+        log::debug!(
+            "next fetch of {} in {:?}",
+            S::NAME,
+            scheduler.wait_remains()
+        );
+
+        let status =
+            FetchStatus::new::<S>(res.as_ref().map(|_| new_updates).map_err(|e| e.to_string()));
+        if let Some(reply) = reply {
+            if reply.send(status.result.clone()).is_err() {
+                log::error!("failed to reply to fetch command");
+            }
+        }
+        if status_tx.send(status).await.is_err() {
+            log::error!("failed to send fetch status to mpsc, dropping");
+        }
+        let Ok(updates) = res else {
+            continue;
+        };
         if updates.is_empty() {
             continue;
         }
@@ -75,3 +162,130 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::{mpsc, oneshot};
+
+    use super::*;
+
+    /// Source, which always returns same posts
+    struct SameSource;
+
+    impl UpdateSource for SameSource {
+        type InitError = ();
+
+        const NAME: &'static str = "test@same";
+
+        fn new() -> Result<Self, Self::InitError> {
+            Ok(Self)
+        }
+    }
+
+    #[async_trait]
+    impl UpdateSourceList for SameSource {
+        async fn get_updates(&self) -> anyhow::Result<UpdatesList> {
+            Ok(UpdatesList {
+                source: Self::NAME,
+                updates: [200, 100]
+                    .into_iter()
+                    .map(|t| Update::builder().app_id("app").update_time(t).build())
+                    .collect(),
+                last_update: 200,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_seen_updates_are_not_counted() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let (status_tx, mut status_rx) = mpsc::channel(10);
+        let (control_tx, control_rx) = mpsc::channel(10);
+        let job = tokio::spawn(start_list_update_loop(
+            SameSource,
+            None,
+            Scheduler::new(ScheduleConfig::default()),
+            tx,
+            status_tx,
+            control_rx,
+            Heartbeat::new(FETCH_STALL_TIMEOUT),
+        ));
+
+        // first fetch is scheduled immediately
+        let status = status_rx.recv().await.unwrap();
+        assert_eq!(status.result, Ok(2));
+        assert_eq!(rx.recv().await.unwrap().count(), 2);
+
+        let (reply, reply_rx) = oneshot::channel();
+        control_tx
+            .send(SourceCommand::Fetch { reply })
+            .await
+            .unwrap();
+        assert_eq!(reply_rx.await.unwrap(), Ok(0));
+        assert_eq!(status_rx.recv().await.unwrap().result, Ok(0));
+
+        job.abort();
+    }
+
+    #[tokio::test]
+    async fn test_updates_seen_before_start_are_not_counted() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let (status_tx, mut status_rx) = mpsc::channel(10);
+        let (_control_tx, control_rx) = mpsc::channel(10);
+        let job = tokio::spawn(start_list_update_loop(
+            SameSource,
+            Some(100),
+            Scheduler::new(ScheduleConfig::default()),
+            tx,
+            status_tx,
+            control_rx,
+            Heartbeat::new(FETCH_STALL_TIMEOUT),
+        ));
+
+        assert_eq!(status_rx.recv().await.unwrap().result, Ok(1));
+        // all updates are sent anyway, notified users are filtered by bot
+        assert_eq!(rx.recv().await.unwrap().count(), 2);
+
+        job.abort();
+    }
+
+    #[tokio::test]
+    async fn test_preview() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let (status_tx, mut status_rx) = mpsc::channel(10);
+        let (control_tx, control_rx) = mpsc::channel(10);
+        let heartbeat = Heartbeat::new(FETCH_STALL_TIMEOUT);
+        let mut control = SourcesControl::default();
+        control.add(SameSource::NAME, control_tx, heartbeat.clone());
+        let job = tokio::spawn(start_list_update_loop(
+            SameSource,
+            None,
+            Scheduler::new(ScheduleConfig::default()),
+            tx,
+            status_tx,
+            control_rx,
+            heartbeat,
+        ));
+        status_rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+
+        assert!(control.preview("other").await.is_empty());
+        let results = control.preview("same").await;
+        assert_eq!(results.len(), 1);
+        let (name, res) = &results[0];
+        assert_eq!(*name, SameSource::NAME);
+        let times = res
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|u| u.update_time())
+            .collect::<Vec<_>>();
+        assert_eq!(times, [200, 100]);
+
+        // updates are not sent further and fetch is not reported
+        assert!(rx.try_recv().is_err());
+        assert!(status_rx.try_recv().is_err());
+
+        job.abort();
+    }
+}
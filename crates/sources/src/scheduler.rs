@@ -0,0 +1,285 @@
+//! Scheduling of fetches for sources
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+/// Source of current time, allows to test scheduling deterministically
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<C: Clock> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        self.as_ref().now()
+    }
+}
+
+/// Fetch schedule for source. Durations are set in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Interval between fetches, when source is neither active nor failing
+    #[serde(rename = "interval_secs", with = "secs")]
+    pub interval: Duration,
+    /// Interval between fetches, when source had updates recently
+    #[serde(rename = "active_interval_secs", with = "secs")]
+    pub active_interval: Duration,
+    /// Source is active, if it had updates during this period
+    #[serde(rename = "active_period_secs", with = "secs")]
+    pub active_period: Duration,
+    /// Max interval, when backing off after errors
+    #[serde(rename = "max_interval_secs", with = "secs")]
+    pub max_interval: Duration,
+    /// Random part of interval, e.g. 0.1 means ±10%
+    pub jitter: f64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60 * 60),
+            active_interval: Duration::from_secs(20 * 60),
+            active_period: Duration::from_secs(6 * 60 * 60),
+            max_interval: Duration::from_secs(6 * 60 * 60),
+            jitter: 0.1,
+        }
+    }
+}
+
+impl ScheduleConfig {
+    /// Check that schedule makes sense, returns description of problem
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.interval.is_zero() || self.active_interval.is_zero() {
+            return Err("intervals should be greater than zero");
+        }
+        if self.max_interval < self.interval {
+            return Err("max_interval_secs should not be less than interval_secs");
+        }
+        if !(0.0..1.0).contains(&self.jitter) {
+            return Err("jitter should be in range [0, 1)");
+        }
+        Ok(())
+    }
+}
+
+mod secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer};
+
+    pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// Decides when source should be fetched next time
+#[derive(Debug)]
+pub(crate) struct Scheduler<C = SystemClock> {
+    config: ScheduleConfig,
+    clock: C,
+    next_fetch: Instant,
+    /// When source had updates last time
+    last_active: Option<Instant>,
+    /// Errors in a row
+    failures: u32,
+}
+
+impl Scheduler {
+    pub(crate) fn new(config: ScheduleConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock> Scheduler<C> {
+    /// First fetch is scheduled immediately
+    pub(crate) fn with_clock(config: ScheduleConfig, clock: C) -> Self {
+        let now = clock.now();
+        Self {
+            config,
+            clock,
+            next_fetch: now,
+            last_active: None,
+            failures: 0,
+        }
+    }
+    /// How long to wait until next fetch
+    pub(crate) fn wait_remains(&self) -> Duration {
+        self.next_fetch.saturating_duration_since(self.clock.now())
+    }
+    /// Schedule next fetch after successful one
+    pub(crate) fn on_success(&mut self, updates_found: usize) {
+        self.failures = 0;
+        if updates_found > 0 {
+            self.last_active = Some(self.clock.now());
+        }
+        self.schedule(None);
+    }
+    /// Schedule next fetch after failed one. If source asked to wait
+    /// (`retry_after`), next fetch will not be earlier
+    pub(crate) fn on_error(&mut self, retry_after: Option<Duration>) {
+        self.failures = self.failures.saturating_add(1);
+        self.schedule(retry_after);
+    }
+    fn schedule(&mut self, min_wait: Option<Duration>) {
+        let now = self.clock.now();
+        let interval = with_jitter(
+            self.base_interval(now),
+            self.config.jitter,
+            rand::random_range(-1.0..=1.0),
+        );
+        self.next_fetch = now + interval.max(min_wait.unwrap_or_default());
+    }
+    /// Interval without jitter
+    fn base_interval(&self, now: Instant) -> Duration {
+        let config = &self.config;
+        if self.failures > 0 {
+            // first error does not change interval, then it doubles
+            let factor = 2u32.saturating_pow(self.failures - 1);
+            return config
+                .interval
+                .saturating_mul(factor)
+                .min(config.max_interval.max(config.interval));
+        }
+        let active = self
+            .last_active
+            .is_some_and(|t| now.saturating_duration_since(t) <= config.active_period);
+        if active {
+            config.active_interval
+        } else {
+            config.interval
+        }
+    }
+}
+
+/// Change `interval` by `jitter * random` part, `random` is in `-1..=1`
+fn with_jitter(interval: Duration, jitter: f64, random: f64) -> Duration {
+    interval.mul_f64((1.0 + jitter * random).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    struct MockClock {
+        now: Mutex<Instant>,
+    }
+
+    impl MockClock {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                now: Mutex::new(Instant::now()),
+            })
+        }
+        fn advance(&self, d: Duration) {
+            *self.now.lock().unwrap() += d;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    fn config() -> ScheduleConfig {
+        ScheduleConfig {
+            interval: 60 * MINUTE,
+            active_interval: 10 * MINUTE,
+            active_period: 120 * MINUTE,
+            max_interval: 180 * MINUTE,
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_scheduler_activity() {
+        let clock = MockClock::new();
+        let mut s = Scheduler::with_clock(config(), clock.clone());
+        assert_eq!(s.wait_remains(), Duration::ZERO);
+
+        s.on_success(0);
+        assert_eq!(s.wait_remains(), 60 * MINUTE);
+        clock.advance(15 * MINUTE);
+        assert_eq!(s.wait_remains(), 45 * MINUTE);
+
+        // had updates, poll more often
+        s.on_success(2);
+        assert_eq!(s.wait_remains(), 10 * MINUTE);
+        clock.advance(100 * MINUTE);
+        s.on_success(0);
+        assert_eq!(s.wait_remains(), 10 * MINUTE);
+
+        // no updates for long time
+        clock.advance(30 * MINUTE);
+        s.on_success(0);
+        assert_eq!(s.wait_remains(), 60 * MINUTE);
+    }
+
+    #[test]
+    fn test_scheduler_backoff() {
+        let clock = MockClock::new();
+        let mut s = Scheduler::with_clock(config(), clock.clone());
+
+        let expected = [60, 120, 180, 180];
+        for (i, minutes) in expected.into_iter().enumerate() {
+            s.on_error(None);
+            assert_eq!(s.wait_remains(), minutes * MINUTE, "error {i}");
+        }
+
+        s.on_success(0);
+        assert_eq!(s.wait_remains(), 60 * MINUTE);
+    }
+
+    #[test]
+    fn test_scheduler_retry_after() {
+        let clock = MockClock::new();
+        let mut s = Scheduler::with_clock(config(), clock.clone());
+
+        s.on_error(Some(300 * MINUTE));
+        assert_eq!(s.wait_remains(), 300 * MINUTE);
+        // shorter wait does not make interval shorter
+        s.on_error(Some(MINUTE));
+        assert_eq!(s.wait_remains(), 120 * MINUTE);
+    }
+
+    #[test]
+    fn test_scheduler_jitter() {
+        let config = ScheduleConfig {
+            jitter: 0.5,
+            ..config()
+        };
+        let mut s = Scheduler::with_clock(config, MockClock::new());
+        for _ in 0..100 {
+            s.on_success(0);
+            let wait = s.wait_remains();
+            assert!(
+                (30 * MINUTE..=90 * MINUTE).contains(&wait),
+                "wait {wait:?} out of range"
+            );
+        }
+
+        assert_eq!(with_jitter(60 * MINUTE, 0.5, -1.0), 30 * MINUTE);
+        assert_eq!(with_jitter(60 * MINUTE, 0.5, 1.0), 90 * MINUTE);
+        assert_eq!(with_jitter(60 * MINUTE, 2.0, -1.0), Duration::ZERO);
+    }
+}
@@ -1,7 +1,4 @@
-use std::time::Duration;
-
 use async_trait::async_trait;
-use timer::Timer;
 
 use crate::extractor::tg::{
     fetch_public_channel, Document, KeyboardButton, Media, Message, ReplyInlineMarkupRow,
@@ -11,20 +8,11 @@ use crate::*;
 
 const CHANNEL_NAME: &str = "alexstranniklite";
 
-pub struct Source {
-    timer: Timer,
-}
+pub struct Source;
 
 impl Source {
-    async fn get_updates_list(&self) -> super::UpdatesList {
-        if self.wait_remains().is_some() {
-            return super::UpdatesList::default();
-        }
-
-        let msgs = match fetch_public_channel(CHANNEL_NAME).await {
-            Ok(v) => v,
-            Err(_) => return super::UpdatesList::default(),
-        };
+    async fn get_updates_list(&self) -> anyhow::Result<super::UpdatesList> {
+        let msgs = fetch_public_channel(CHANNEL_NAME).await?;
 
         let mut last_update = None;
 
@@ -64,36 +52,28 @@ impl Source {
                 msg_with_update = None;
             }
         }
-        super::UpdatesList {
+        Ok(super::UpdatesList {
+            source: Self::NAME,
             updates,
             last_update: last_update.unwrap_or_default(),
-        }
+        })
     }
 }
 
-#[async_trait]
 impl UpdateSource for Source {
     // such type to use log_error when creating source
     type InitError = &'static str;
 
-    fn with_timeout(timeout: Duration) -> Result<Self, Self::InitError> {
-        Ok(Self {
-            timer: Timer::new(timeout),
-        })
-    }
-
-    fn wait_remains(&self) -> Option<Duration> {
-        self.timer.elapsed_remains()
-    }
+    const NAME: &'static str = "tg@alexstranniklite";
 
-    fn reset_timer(&self) {
-        self.timer.reset()
+    fn new() -> Result<Self, Self::InitError> {
+        Ok(Self)
     }
 }
 
 #[async_trait]
 impl UpdateSourceList for Source {
-    async fn get_updates(&self) -> super::UpdatesList {
+    async fn get_updates(&self) -> anyhow::Result<super::UpdatesList> {
         self.get_updates_list().await
     }
 }
@@ -1,33 +1,119 @@
-use tokio::{sync::mpsc::Sender, task::JoinSet};
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tokio::{
+    sync::mpsc::{self, Sender},
+    task::JoinSet,
+};
 use tokio_util::sync::CancellationToken;
 
-use common::spawn_with_token;
+use common::{spawn_with_token, Heartbeat, UnixDateTime};
 
-use crate::{start_list_update_loop, UpdateSource, UpdatesList};
+use crate::{
+    control::matches_source, scheduler::Scheduler, start_list_update_loop, FetchStatus,
+    PreviewResult, ScheduleConfig, SourcesControl, UpdateSource, UpdateSourceList, UpdatesList,
+    FETCH_STALL_TIMEOUT,
+};
 
 mod alexstranniklite;
 
+/// Names of all sources. Should be in sync with list in
+/// [`spawn_sources_update_jobs`]
+pub const SOURCE_NAMES: &[&str] = &[<alexstranniklite::Source as UpdateSource>::NAME];
+
+/// Configuration of source
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SourceConfig {
+    /// Disabled sources are not fetched
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            schedule: ScheduleConfig::default(),
+        }
+    }
+}
+
 macro_rules! spawn_list_sources {
     () => {};
-    ($jobs:ident, $token:ident, $tx:ident; $($module:ident),* $(,)?) => {
+    ($jobs:ident, $token:ident, $tx:ident, $status_tx:ident, $control:ident, $configs:ident, $last_updates:ident; $($module:ident),* $(,)?) => {
         $(
-            match $module::Source::new() {
-                Ok(source) => {
-                    $jobs.spawn(spawn_with_token(
-                        $token.clone(),
-                        start_list_update_loop(source, $tx.clone()),
-                    ));
-                },
-                Err(e) => log::error!("failed to start source {}: {e}", stringify!($module)),
+            let name = <$module::Source as UpdateSource>::NAME;
+            let config = $configs.get(name).copied().unwrap_or_default();
+            if !config.enabled {
+                log::info!("source {name} is disabled");
+            } else {
+                match $module::Source::new() {
+                    Ok(source) => {
+                        let (control_tx, control_rx) = mpsc::channel(10);
+                        let heartbeat = Heartbeat::new(FETCH_STALL_TIMEOUT);
+                        $control.add(name, control_tx, heartbeat.clone());
+                        $jobs.spawn(spawn_with_token(
+                            $token.clone(),
+                            start_list_update_loop(
+                                source,
+                                $last_updates.get(name).copied(),
+                                Scheduler::new(config.schedule),
+                                $tx.clone(),
+                                $status_tx.clone(),
+                                control_rx,
+                                heartbeat,
+                            ),
+                        ));
+                    },
+                    Err(e) => log::error!("failed to start source {name}: {e}"),
+                }
             }
         )*
     };
 }
 
+macro_rules! preview_list_sources {
+    () => {};
+    ($query:ident, $results:ident; $($module:ident),* $(,)?) => {
+        $(
+            let name = <$module::Source as UpdateSource>::NAME;
+            if matches_source(name, $query) {
+                let res = match $module::Source::new() {
+                    Ok(source) => source
+                        .get_updates()
+                        .await
+                        .map(|u| u.updates)
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(format!("failed to start source: {e}")),
+                };
+                $results.push((name, res));
+            }
+        )*
+    };
+}
+
+/// Fetch sources, matching `query` (see [`SourcesControl::preview`]), once,
+/// without starting update loops. Disabled sources are fetched too
+pub async fn preview_sources(query: &str) -> Vec<(&'static str, PreviewResult)> {
+    let mut results = vec![];
+    preview_list_sources![query, results; alexstranniklite];
+    results
+}
+
+/// Spawn update loops for all enabled sources. Sources without config in
+/// `configs` (by [`UpdateSource::NAME`]) use default one. `last_updates` are
+/// times of newest updates, already seen by bot, by source name
 pub fn spawn_sources_update_jobs(
     jobs: &mut JoinSet<()>,
     token: CancellationToken,
     tx: Sender<UpdatesList>,
-) {
-    spawn_list_sources![jobs, token, tx; alexstranniklite];
+    status_tx: Sender<FetchStatus>,
+    configs: &HashMap<String, SourceConfig>,
+    last_updates: &HashMap<String, UnixDateTime>,
+) -> SourcesControl {
+    let mut control = SourcesControl::default();
+    spawn_list_sources![jobs, token, tx, status_tx, control, configs, last_updates; alexstranniklite];
+    control
 }
@@ -0,0 +1,19 @@
+use crate::{FetchResult, UpdateSource};
+
+/// Result of fetching source, used to track health of sources
+#[derive(Debug)]
+pub struct FetchStatus {
+    /// See [`UpdateSource::NAME`]
+    pub source: &'static str,
+    /// Count of new updates, or error
+    pub result: FetchResult,
+}
+
+impl FetchStatus {
+    pub(crate) fn new<S: UpdateSource>(result: FetchResult) -> Self {
+        Self {
+            source: S::NAME,
+            result,
+        }
+    }
+}
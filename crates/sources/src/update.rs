@@ -4,6 +4,8 @@ use common::UnixDateTime;
 
 #[derive(Debug, Default)]
 pub struct UpdatesList {
+    /// See [`UpdateSource::NAME`](crate::UpdateSource::NAME)
+    pub source: &'static str,
     pub updates: Vec<Update>,
     pub last_update: UnixDateTime,
 }
@@ -15,6 +17,17 @@ impl UpdatesList {
     pub fn count(&self) -> usize {
         self.updates.len()
     }
+    /// Count of updates newer than `since`, all updates if it's not known
+    pub(crate) fn count_newer(&self, since: Option<UnixDateTime>) -> usize {
+        match since {
+            Some(since) => self
+                .updates
+                .iter()
+                .filter(|u| u.update_time > since)
+                .count(),
+            None => self.count(),
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -20,6 +20,7 @@
               just
               cargo-nextest
               sqlite
+              postgresql
             ];
             buildInputs = [];
             CROSS_ROOTLESS_CONTAINER_ENGINE = 1;
@@ -8,7 +8,18 @@ welcome-suggest-subscribe = { welcome }
     Language saved. Subscribe to keep track of updates: /subscribe
 
 new-app-msg = New app to track updates:
+new-app-msg-minimal = New app: { $app }
+new-app-msg-detailed =
+    New app to track updates: { $app }
+    { update-time }
 new-update-msg = Update for { $app }
+new-update-msg-minimal = { $app } updated
+new-update-msg-detailed =
+    { new-update-msg }
+    { update-time }
+update-time = Published: { $time }
+update-description-link = Description:
+update-link = Update:
 subscribed = Subscribed
 unsubscribed = Unsubscribed
 
@@ -43,6 +54,8 @@ unknown-message = Try /help
 notify-button = Notify
 ignore-button = Ignore
 see-update-button = See update
+back-button = « Back
+close-button = ✖ Close
 
 ## Misc
 
@@ -67,6 +80,34 @@ notifications-disabled = { -notifications } disabled
 
 lang-saved = Language saved
 
+## Settings
+
+settings-header = Settings
+settings-closed = Settings saved
+settings-language-button = Language
+settings-verbosity-button = Notifications format
+settings-delivery-button = Notifications delivery
+settings-timezone-button = Timezone
+settings-sources-button = Sources
+settings-verbosity-header = How detailed should notifications about updates be?
+verbosity-minimal = Minimal
+verbosity-normal = Normal
+verbosity-detailed = Detailed
+verbosity-saved = Notifications format saved
+settings-delivery-header = How should notifications be delivered?
+delivery-normal = With sound
+delivery-silent = Silently
+delivery-saved = Notifications delivery saved
+settings-timezone-header = Your timezone: { $timezone }. Time in notifications is shown in this timezone.
+timezone-saved = Timezone saved
+settings-sources-header = Sources of updates. Tap a source to subscribe or unsubscribe.
+new-sources-ask-button = New sources: ask me
+new-sources-subscribe-button = New sources: subscribe automatically
+new-sources-saved = Saved
+subscribe-button = Subscribe
+new-source-msg = New source of updates: { $source }
+new-source-subscribed-msg = You have been subscribed to new source of updates: { $source }
+
 not-implemented-already-subscribed = You are already subscribed
 
 ## Commands descriptions
@@ -78,11 +119,23 @@ changelog-command = Changelog
 settings-command = Configuration
 about-command = About this bot
 help-command = Display help
+mydata-command = Show all data, which bot stores about you
+deleteme-command = Delete all your data from bot
 
 # Admin commands
 
 admin-commands-header = *Commands for admin:*
 stats-command = Statistics
+broadcast-command = Send message to all users
+user-command = Show user, by id or @username
+users-command = Recently joined users
+ban-command = Ban user, by id or @username
+unban-command = Unban user, by id or @username
+sources-command = Status of sources
+fetch-command = Fetch sources now, all or specified
+preview-command = Show updates, which sources would produce now, without sending them
+backup-command = Send backup of database
+admin-command-forbidden = This command requires role { $role }
 
 ## Stats
 
@@ -92,6 +145,78 @@ stats-sources = Sources
 stats-users = Total users
 stats-users-blocked = Users who have blocked the bot
 
+## Users
+
+user-usage = Specify user id or @username
+user-not-found = User not found
+user-info-header = *User*
+user-info-id = ID
+user-info-lang = Language
+user-info-joined = Joined
+user-info-last-notified = Last notified
+user-info-bot-blocked = Blocked bot
+user-info-banned = Banned
+user-info-settings = Settings
+user-info-subscriptions = Subscriptions
+user-info-apps = Followed apps
+user-info-yes = yes
+user-info-no = no
+user-info-unknown = unknown
+user-info-none = none
+users-header = *Recently joined users*
+user-banned = User { $user } banned
+user-unbanned = User { $user } unbanned
+user-ban-admin = Admin can't be banned
+
+## Sources health
+
+source-health-header = Sources status
+source-health-never = never
+source-health-last-success = Last successful fetch: { $time }
+source-health-updates-found = Updates found: { $count }
+source-health-failing = Failures in a row: { $count }, since { $since }
+source-health-last-error = Last error ({ $time }): { $error }
+source-failing-alert = Source { $source } is failing since { $since } ({ $count } failures in a row). Last error: { $error }
+source-recovered-alert = Source { $source } recovered
+fetch-started = Fetching…
+fetch-source-not-found = Source not found. Running sources: { $sources }
+fetch-result = { $source }: found { $count } updates
+fetch-failed = { $source }: failed: { $error }
+preview-header = { $source }: { $count } updates
+preview-failed = { $source }: failed: { $error }
+preview-update-time = Time: { $time }
+backup-failed = Failed to send backup: { $error }
+
+## Broadcast
+
+broadcast-usage = Usage: /broadcast text. Text for other languages can be added after lines like [ru]
+broadcast-preview = Broadcast will be sent to { $count } users:
+broadcast-default-text = [default]
+broadcast-confirm-button = Send
+broadcast-cancel-button = Cancel
+broadcast-confirmed = Broadcast confirmed, sending
+broadcast-cancelled = Broadcast cancelled
+broadcast-already-handled = Broadcast is already confirmed or cancelled
+broadcast-progress = Broadcast #{ $id }: sent { $sent }, failed { $failed }, blocked { $blocked }, left { $left }
+broadcast-finished = Broadcast #{ $id } finished: sent { $sent }, failed { $failed }, blocked { $blocked }
+
+## Your data
+
+mydata-header = Data, which bot stores about you:
+mydata-username = Username
+mydata-name = Name
+mydata-ignored-apps = Ignored apps
+mydata-version-notified = Last notified about bot version
+mydata-broadcasts = Broadcasts
+mydata-settings-menu = Settings menu
+mydata-footer = To delete all of it, use /deleteme
+mydata-empty = Bot doesn't store any data about you
+deleteme-confirm = All your subscriptions, settings and choices about apps will be deleted. This can't be undone. Continue?
+deleteme-confirm-button = Delete
+deleteme-cancel-button = Cancel
+deleteme-done = Your data is deleted. Send /start to use bot again
+deleteme-cancelled = Deletion cancelled
+
 ## Changelog
 
 changelog-header = *What's new:*
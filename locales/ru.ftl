@@ -8,7 +8,18 @@ welcome-suggest-subscribe = { welcome }
     Язык сохранен. Подписаться, чтобы отслеживать обновления: /subscribe
 
 new-app-msg = Новое приложение для отслеживания:
+new-app-msg-minimal = Новое приложение: { $app }
+new-app-msg-detailed =
+    Новое приложение для отслеживания: { $app }
+    { update-time }
 new-update-msg = Обновление для { $app }
+new-update-msg-minimal = { $app } обновлено
+new-update-msg-detailed =
+    { new-update-msg }
+    { update-time }
+update-time = Опубликовано: { $time }
+update-description-link = Описание:
+update-link = Обновление:
 subscribed = Вы подписаны
 unsubscribed = Вы отписаны
 
@@ -43,6 +54,8 @@ unknown-message = Попробуйте /help
 notify-button = Уведомлять
 ignore-button = Игнорировать
 see-update-button = Посмотреть обновление
+back-button = « Назад
+close-button = ✖ Закрыть
 
 ## Misc
 
@@ -66,6 +79,34 @@ notifications-disabled = { -notifications } выключены
 
 lang-saved = Язык сохранён
 
+## Settings
+
+settings-header = Настройки
+settings-closed = Настройки сохранены
+settings-language-button = Язык
+settings-verbosity-button = Формат уведомлений
+settings-delivery-button = Доставка уведомлений
+settings-timezone-button = Часовой пояс
+settings-sources-button = Источники
+settings-verbosity-header = Насколько подробными должны быть уведомления об обновлениях?
+verbosity-minimal = Кратко
+verbosity-normal = Обычно
+verbosity-detailed = Подробно
+verbosity-saved = Формат уведомлений сохранён
+settings-delivery-header = Как доставлять уведомления?
+delivery-normal = Со звуком
+delivery-silent = Без звука
+delivery-saved = Доставка уведомлений сохранена
+settings-timezone-header = Ваш часовой пояс: { $timezone }. Время в уведомлениях показывается в этом часовом поясе.
+timezone-saved = Часовой пояс сохранён
+settings-sources-header = Источники обновлений. Нажмите на источник, чтобы подписаться или отписаться.
+new-sources-ask-button = Новые источники: спрашивать
+new-sources-subscribe-button = Новые источники: подписываться автоматически
+new-sources-saved = Сохранено
+subscribe-button = Подписаться
+new-source-msg = Новый источник обновлений: { $source }
+new-source-subscribed-msg = Вы подписаны на новый источник обновлений: { $source }
+
 not-implemented-already-subscribed = Вы уже подписаны
 
 ## Commands descriptions
@@ -77,11 +118,23 @@ changelog-command = Список изменений
 settings-command = Настройки
 about-command = Об этом боте
 help-command = Показать справку
+mydata-command = Показать все данные, которые бот хранит о вас
+deleteme-command = Удалить все ваши данные из бота
 
 # Admin commands
 
 admin-commands-header = *Команды для админа:*
 stats-command = Статистика
+broadcast-command = Отправить сообщение всем пользователям
+user-command = Показать пользователя, по id или @username
+users-command = Недавно присоединившиеся пользователи
+ban-command = Забанить пользователя, по id или @username
+unban-command = Разбанить пользователя, по id или @username
+sources-command = Состояние источников
+fetch-command = Получить обновления из источников сейчас, всех или указанного
+preview-command = Показать обновления, которые источники найдут сейчас, без отправки
+backup-command = Прислать резервную копию базы данных
+admin-command-forbidden = Для этой команды нужна роль { $role }
 
 ## Stats
 
@@ -91,6 +144,78 @@ stats-sources = Источников
 stats-users = Всего пользователей
 stats-users-blocked = Пользователей, заблокировавших бота
 
+## Users
+
+user-usage = Укажите id пользователя или @username
+user-not-found = Пользователь не найден
+user-info-header = *Пользователь*
+user-info-id = ID
+user-info-lang = Язык
+user-info-joined = Присоединился
+user-info-last-notified = Последнее уведомление
+user-info-bot-blocked = Заблокировал бота
+user-info-banned = Забанен
+user-info-settings = Настройки
+user-info-subscriptions = Подписки
+user-info-apps = Отслеживаемые приложения
+user-info-yes = да
+user-info-no = нет
+user-info-unknown = неизвестно
+user-info-none = нет
+users-header = *Недавно присоединившиеся пользователи*
+user-banned = Пользователь { $user } забанен
+user-unbanned = Пользователь { $user } разбанен
+user-ban-admin = Админа нельзя забанить
+
+## Sources health
+
+source-health-header = Состояние источников
+source-health-never = никогда
+source-health-last-success = Последнее успешное получение: { $time }
+source-health-updates-found = Найдено обновлений: { $count }
+source-health-failing = Ошибок подряд: { $count }, начиная с { $since }
+source-health-last-error = Последняя ошибка ({ $time }): { $error }
+source-failing-alert = Источник { $source } не работает с { $since } ({ $count } ошибок подряд). Последняя ошибка: { $error }
+source-recovered-alert = Источник { $source } снова работает
+fetch-started = Получение…
+fetch-source-not-found = Источник не найден. Запущенные источники: { $sources }
+fetch-result = { $source }: найдено обновлений: { $count }
+fetch-failed = { $source }: ошибка: { $error }
+preview-header = { $source }: обновлений: { $count }
+preview-failed = { $source }: ошибка: { $error }
+preview-update-time = Время: { $time }
+backup-failed = Не удалось отправить резервную копию: { $error }
+
+## Broadcast
+
+broadcast-usage = Использование: /broadcast текст. Текст для других языков можно добавить после строк вида [en]
+broadcast-preview = Рассылка будет отправлена { $count } пользователям:
+broadcast-default-text = [по умолчанию]
+broadcast-confirm-button = Отправить
+broadcast-cancel-button = Отменить
+broadcast-confirmed = Рассылка подтверждена, отправляется
+broadcast-cancelled = Рассылка отменена
+broadcast-already-handled = Рассылка уже подтверждена или отменена
+broadcast-progress = Рассылка #{ $id }: отправлено { $sent }, ошибок { $failed }, заблокировали { $blocked }, осталось { $left }
+broadcast-finished = Рассылка #{ $id } завершена: отправлено { $sent }, ошибок { $failed }, заблокировали { $blocked }
+
+## Ваши данные
+
+mydata-header = Данные, которые бот хранит о вас:
+mydata-username = Имя пользователя
+mydata-name = Имя
+mydata-ignored-apps = Игнорируемые приложения
+mydata-version-notified = Последняя версия бота, о которой вы получили уведомление
+mydata-broadcasts = Рассылки
+mydata-settings-menu = Меню настроек
+mydata-footer = Чтобы удалить их все, используйте /deleteme
+mydata-empty = Бот не хранит никаких данных о вас
+deleteme-confirm = Все ваши подписки, настройки и выбор по приложениям будут удалены. Это нельзя отменить. Продолжить?
+deleteme-confirm-button = Удалить
+deleteme-cancel-button = Отмена
+deleteme-done = Ваши данные удалены. Отправьте /start, чтобы снова пользоваться ботом
+deleteme-cancelled = Удаление отменено
+
 ## Changelog
 
 changelog-header = *Что нового:*
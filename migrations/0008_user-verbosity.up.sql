@@ -0,0 +1,2 @@
+-- how detailed notifications about updates should be
+alter table user add column verbosity text not null default 'normal';
@@ -0,0 +1,2 @@
+alter table user drop column delivery_mode;
+alter table user drop column utc_offset;
@@ -0,0 +1,5 @@
+-- how notifications are delivered, see models::DeliveryMode
+alter table user add column delivery_mode text not null default 'normal';
+
+-- user's timezone, minutes from UTC
+alter table user add column utc_offset int not null default 0;
@@ -0,0 +1,2 @@
+alter table source drop column description;
+alter table user drop column new_sources;
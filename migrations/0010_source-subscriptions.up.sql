@@ -0,0 +1,9 @@
+-- shown to users when choosing sources to subscribe
+alter table source add column description text not null default '';
+
+update source
+	set description = 'Modified Android apps'
+	where source_id = 1;
+
+-- what to do when new source is added, see models::NewSourcesMode
+alter table user add column new_sources text not null default 'ask';
@@ -0,0 +1,3 @@
+drop table broadcast_delivery;
+drop table broadcast_text;
+drop table broadcast;
@@ -0,0 +1,24 @@
+create table broadcast (
+	broadcast_id integer primary key,
+	admin_chat_id int not null, -- where to send progress
+	status text not null, -- see models::BroadcastStatus
+	created_at int not null -- unix time
+);
+
+-- message texts for different languages, '' is for default text
+create table broadcast_text (
+	broadcast_id int not null,
+	lang text not null,
+	text text not null,
+
+	primary key (broadcast_id, lang)
+);
+
+-- users, to which broadcast was already sent, to resume after restart
+create table broadcast_delivery (
+	broadcast_id int not null,
+	user_id int not null,
+	status text not null, -- see models::DeliveryStatus
+
+	primary key (broadcast_id, user_id)
+);
@@ -0,0 +1,2 @@
+alter table user drop column joined_at;
+alter table user drop column banned;
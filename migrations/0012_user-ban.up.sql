@@ -0,0 +1,5 @@
+-- banned users can't interact with bot
+alter table user add column banned int not null default false; -- bool
+
+-- unix time, 0 for users added before this column
+alter table user add column joined_at int not null default 0;
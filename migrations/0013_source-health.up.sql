@@ -0,0 +1,10 @@
+create table source_health (
+	source_id int primary key,
+	last_success_at int not null default 0, -- unix time
+	last_error text not null default '',
+	last_error_at int not null default 0, -- unix time
+	failing_since int not null default 0, -- unix time of first failure in a row, 0 if not failing
+	consecutive_failures int not null default 0,
+	updates_found int not null default 0, -- total
+	alerted int not null default false -- bool, admin was alerted about failure
+);
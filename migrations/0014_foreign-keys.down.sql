@@ -0,0 +1,55 @@
+-- Children are rebuilt first, so dropping parent doesn't cascade
+
+create table user_update_old (
+	user_id int not null,
+	source_id int not null,
+	app_id text not null,
+	should_notify int not null, -- bool
+
+	primary key (user_id, source_id, app_id)
+);
+insert into user_update_old select * from user_update;
+drop table user_update;
+alter table user_update_old rename to user_update;
+
+create table user_subscribe_old (
+	user_id int not null,
+	source_id int not null,
+	subscribed int not null, -- bool
+
+	primary key (user_id, source_id)
+);
+insert into user_subscribe_old select * from user_subscribe;
+drop table user_subscribe;
+alter table user_subscribe_old rename to user_subscribe;
+
+create table app_old (
+	app_id text,
+	source_id int,
+	name text,
+	last_updated_at int default 0, -- unix time
+
+	primary key (app_id, source_id)
+);
+insert into app_old select * from app;
+drop table app;
+alter table app_old rename to app;
+
+create table user_old (
+	user_id int primary key,
+	lang text not null,
+	last_notified_at int default 0, -- unix time
+	last_version_notified integer default 0,
+	bot_blocked int not null default false,
+	username int default '',
+	name int default '',
+	verbosity text not null default 'normal',
+	delivery_mode text not null default 'normal',
+	utc_offset int not null default 0,
+	new_sources text not null default 'ask',
+	banned int not null default false, -- bool
+	joined_at int not null default 0 -- unix time
+);
+insert into user_old select * from user;
+drop table user;
+alter table user_old rename to user;
@@ -0,0 +1,97 @@
+-- Tables are rebuilt, because sqlite can't change column types or add
+-- foreign keys to existing tables. Parent table is rebuilt first, while no
+-- foreign keys reference it, so dropping it doesn't cascade. Rows of users
+-- and sources, which don't exist anymore, are not copied
+
+-- username and name were declared as int, so numeric values were stored as
+-- integers
+create table user_new (
+	user_id int primary key,
+	lang text not null,
+	last_notified_at int default 0, -- unix time
+	last_version_notified integer default 0,
+	bot_blocked int not null default false,
+	username text default '',
+	name text default '', -- first name + last name
+	verbosity text not null default 'normal',
+	delivery_mode text not null default 'normal',
+	utc_offset int not null default 0,
+	new_sources text not null default 'ask',
+	banned int not null default false, -- bool
+	joined_at int not null default 0 -- unix time
+);
+
+insert into user_new
+	select user_id, lang, last_notified_at, last_version_notified, bot_blocked,
+		cast(username as text), cast(name as text), verbosity, delivery_mode,
+		utc_offset, new_sources, banned, joined_at
+	from user;
+
+drop table user;
+alter table user_new rename to user;
+
+create index user_username_idx on user (username collate nocase);
+
+create table app_new (
+	app_id text not null,
+	source_id int not null,
+	name text,
+	last_updated_at int default 0, -- unix time
+
+	primary key (app_id, source_id),
+	foreign key (source_id) references source (source_id) on delete cascade
+);
+
+insert into app_new
+	select app_id, source_id, name, last_updated_at
+	from app
+	where source_id in (select source_id from source);
+
+drop table app;
+alter table app_new rename to app;
+
+create index app_source_idx on app (source_id);
+
+create table user_subscribe_new (
+	user_id int not null,
+	source_id int not null,
+	subscribed int not null, -- bool
+
+	primary key (user_id, source_id),
+	foreign key (user_id) references user (user_id) on delete cascade,
+	foreign key (source_id) references source (source_id) on delete cascade
+);
+
+insert into user_subscribe_new
+	select user_id, source_id, subscribed
+	from user_subscribe
+	where user_id in (select user_id from user)
+		and source_id in (select source_id from source);
+
+drop table user_subscribe;
+alter table user_subscribe_new rename to user_subscribe;
+
+-- subscribers of source, used when notifying about updates
+create index user_subscribe_source_idx on user_subscribe (source_id, subscribed);
+
+create table user_update_new (
+	user_id int not null,
+	source_id int not null,
+	app_id text not null,
+	should_notify int not null, -- bool
+
+	primary key (user_id, source_id, app_id),
+	foreign key (user_id) references user (user_id) on delete cascade,
+	foreign key (source_id) references source (source_id) on delete cascade
+);
+
+insert into user_update_new
+	select user_id, source_id, app_id, should_notify
+	from user_update
+	where user_id in (select user_id from user)
+		and source_id in (select source_id from source);
+
+drop table user_update;
+alter table user_update_new rename to user_update;
+
+create index user_update_source_idx on user_update (source_id, app_id);
@@ -0,0 +1,2 @@
+-- Table is kept, because previous versions use it for teloxide storage
+select 1;
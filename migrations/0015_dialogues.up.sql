@@ -0,0 +1,6 @@
+-- State of dialogues, e.g. /settings menu. Table was created by teloxide
+-- storage before, so it may exist
+create table if not exists teloxide_dialogues (
+	chat_id bigint primary key,
+	dialogue blob not null -- serialized state
+);
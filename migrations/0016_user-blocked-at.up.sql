@@ -0,0 +1,6 @@
+-- unix time, when user blocked bot, 0 if not blocked. Users, who blocked
+-- bot before this column, are counted from now
+alter table user add column bot_blocked_at int not null default 0;
+
+update user set bot_blocked_at = cast(strftime('%s', 'now') as int)
+	where bot_blocked = true;
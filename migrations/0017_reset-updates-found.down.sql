@@ -0,0 +1,2 @@
+-- Counters can't be restored
+select 1;
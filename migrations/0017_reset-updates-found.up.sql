@@ -0,0 +1,2 @@
+-- updates_found counted also already seen updates of every fetch
+update source_health set updates_found = 0;
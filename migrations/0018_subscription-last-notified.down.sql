@@ -0,0 +1 @@
+alter table user_subscribe drop column last_notified_at;
@@ -0,0 +1,8 @@
+-- unix time, when user was last notified about updates of source. Updates
+-- of one source don't hide older updates of other sources
+alter table user_subscribe add column last_notified_at int not null default 0;
+
+update user_subscribe set last_notified_at = coalesce(
+	(select u.last_notified_at from user u where u.user_id = user_subscribe.user_id),
+	0
+);
@@ -0,0 +1,9 @@
+drop table source_health;
+drop table broadcast_delivery;
+drop table broadcast_text;
+drop table broadcast;
+drop table user_update;
+drop table user_subscribe;
+drop table app;
+drop table "user";
+drop table source;
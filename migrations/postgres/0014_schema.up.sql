@@ -0,0 +1,102 @@
+-- Schema of sqlite database at version 14, later migrations are added to
+-- both directories with the same version
+
+create table source (
+	source_id bigint primary key,
+	name text,
+	last_updated_at bigint default 0, -- unix time
+	description text not null default ''
+);
+
+insert into source (source_id, name, description)
+	values (1, 'tg@alexstranniklite', 'Modified Android apps');
+
+create table "user" (
+	user_id bigint primary key,
+	lang text not null,
+	last_notified_at bigint default 0, -- unix time
+	last_version_notified bigint default 0,
+	bot_blocked boolean not null default false,
+	username text default '',
+	name text default '', -- first name + last name
+	verbosity text not null default 'normal',
+	delivery_mode text not null default 'normal',
+	utc_offset integer not null default 0,
+	new_sources text not null default 'ask',
+	banned boolean not null default false,
+	joined_at bigint not null default 0 -- unix time
+);
+
+create index user_username_idx on "user" (lower(username));
+
+create table app (
+	app_id text not null,
+	source_id bigint not null,
+	name text,
+	last_updated_at bigint default 0, -- unix time
+
+	primary key (app_id, source_id),
+	foreign key (source_id) references source (source_id) on delete cascade
+);
+
+create index app_source_idx on app (source_id);
+
+create table user_subscribe (
+	user_id bigint not null,
+	source_id bigint not null,
+	subscribed boolean not null,
+
+	primary key (user_id, source_id),
+	foreign key (user_id) references "user" (user_id) on delete cascade,
+	foreign key (source_id) references source (source_id) on delete cascade
+);
+
+-- subscribers of source, used when notifying about updates
+create index user_subscribe_source_idx on user_subscribe (source_id, subscribed);
+
+create table user_update (
+	user_id bigint not null,
+	source_id bigint not null,
+	app_id text not null,
+	should_notify boolean not null,
+
+	primary key (user_id, source_id, app_id),
+	foreign key (user_id) references "user" (user_id) on delete cascade,
+	foreign key (source_id) references source (source_id) on delete cascade
+);
+
+create index user_update_source_idx on user_update (source_id, app_id);
+
+create table broadcast (
+	broadcast_id bigint generated by default as identity primary key,
+	admin_chat_id bigint not null, -- where to send progress
+	status text not null, -- see models::BroadcastStatus
+	created_at bigint not null -- unix time
+);
+
+create table broadcast_text (
+	broadcast_id bigint not null,
+	lang text not null,
+	text text not null,
+
+	primary key (broadcast_id, lang)
+);
+
+create table broadcast_delivery (
+	broadcast_id bigint not null,
+	user_id bigint not null,
+	status text not null, -- see models::DeliveryStatus
+
+	primary key (broadcast_id, user_id)
+);
+
+create table source_health (
+	source_id bigint primary key,
+	last_success_at bigint not null default 0, -- unix time
+	last_error text not null default '',
+	last_error_at bigint not null default 0, -- unix time
+	failing_since bigint not null default 0, -- unix time of first failure in a row, 0 if not failing
+	consecutive_failures bigint not null default 0,
+	updates_found bigint not null default 0, -- total
+	alerted boolean not null default false -- admin was alerted about failure
+);
@@ -0,0 +1 @@
+drop table teloxide_dialogues;
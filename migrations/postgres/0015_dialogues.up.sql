@@ -0,0 +1,5 @@
+-- State of dialogues, e.g. /settings menu
+create table teloxide_dialogues (
+	chat_id bigint primary key,
+	dialogue bytea not null -- serialized state
+);
@@ -0,0 +1,6 @@
+-- unix time, when user blocked bot, 0 if not blocked. Users, who blocked
+-- bot before this column, are counted from now
+alter table "user" add column bot_blocked_at bigint not null default 0;
+
+update "user" set bot_blocked_at = extract(epoch from now())::bigint
+	where bot_blocked = true;
@@ -0,0 +1,286 @@
+//! Command line interface: running bot and maintenance commands
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use reqwest::Client;
+use teloxide::Bot;
+use tokio_util::sync::CancellationToken;
+
+use bot_handlers::{parse_broadcast, run_broadcast};
+use common::{AdminRole, DateTime};
+use db::{
+    models::{BroadcastStatus, User},
+    AdminRepo, BroadcastRepo, UserRepo, DB,
+};
+use sources::preview_sources;
+
+use crate::{
+    config::{Config, DbBackend},
+    BOT_REQUEST_TIMEOUT, NOISY_LOG_TARGETS,
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Run bot, default command
+    Run,
+    /// Apply new migrations, or revert migrations newer than version
+    Migrate {
+        #[arg(long, value_name = "VERSION")]
+        revert_to: Option<i64>,
+    },
+    /// Print count of users, apps and sources
+    Stats,
+    /// Print all users as CSV
+    ExportUsers,
+    /// Send broadcast from file. Format is same as of /broadcast command:
+    /// default text, followed by texts after `[lang]` lines
+    Broadcast {
+        #[arg(long)]
+        file: PathBuf,
+        /// Chat to report progress, first owner from config by default
+        #[arg(long)]
+        admin: Option<i64>,
+    },
+    /// Fetch sources once and print found updates, without saving them
+    CheckSources {
+        /// Name of source, all sources if not set
+        query: Option<String>,
+    },
+    /// Write consistent copy of database to new file
+    DbBackup { path: PathBuf },
+    /// Replace database with backup and apply new migrations. Current
+    /// database is kept with `.old` suffix. Bot should be stopped
+    DbRestore { path: PathBuf },
+}
+
+pub(crate) async fn execute(command: Command, config: Config) -> Result<()> {
+    if !matches!(command, Command::Run) {
+        init_logger();
+    }
+    match command {
+        Command::Run => crate::run(config).await?,
+        Command::Migrate { revert_to } => {
+            let version = match revert_to {
+                Some(version) => config.db.revert_migrations(version).await?,
+                None => {
+                    let db = config.db.open().await?;
+                    let version = db.migration_version().await?;
+                    db.close().await;
+                    version
+                }
+            };
+            println!("database version: {version}");
+        }
+        Command::Stats => {
+            let db = open_db(&config).await?;
+            let stats = db.load_stats().await?;
+            println!("users: {}", stats.users);
+            println!("blocked users: {}", stats.blocked_users);
+            println!("apps: {}", stats.apps);
+            println!("sources: {}", stats.sources);
+        }
+        Command::ExportUsers => {
+            let db = open_db(&config).await?;
+            println!("user_id,username,name,lang,joined_at,bot_blocked,banned");
+            for user in db.select_all_users().await? {
+                println!("{}", user_csv(&user));
+            }
+        }
+        Command::Broadcast { file, admin } => {
+            let text = tokio::fs::read_to_string(&file)
+                .await
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            let Some(texts) = parse_broadcast(&text) else {
+                bail!("broadcast should start with default text, languages should not repeat");
+            };
+            let admin = admin
+                .or_else(|| {
+                    config
+                        .admin
+                        .chats
+                        .iter()
+                        .find(|c| c.role == AdminRole::Owner)
+                        .map(|c| c.id)
+                })
+                .context("no owner in config, set chat with --admin")?;
+
+            let db = open_db(&config).await?;
+            let broadcast_id = db.add_broadcast(admin, &texts).await?;
+            db.save_broadcast_status(
+                broadcast_id,
+                BroadcastStatus::Draft,
+                BroadcastStatus::Running,
+            )
+            .await?;
+            println!("sending broadcast {broadcast_id}, progress is reported to chat {admin}");
+
+            let token = CancellationToken::new();
+            let bot = Bot::with_client(
+                &config.bot.token,
+                Client::builder().timeout(BOT_REQUEST_TIMEOUT).build()?,
+            );
+            let run = run_broadcast(&bot, &db, broadcast_id, &token);
+            tokio::pin!(run);
+            tokio::select! {
+                res = &mut run => res?,
+                _ = tokio::signal::ctrl_c() => {
+                    // broadcast stops after current user
+                    token.cancel();
+                    run.await?;
+                    println!("interrupted, broadcast is resumed by bot on start");
+                    return Ok(());
+                }
+            }
+            let progress = db.load_broadcast_progress(broadcast_id).await?;
+            println!("{progress:?}");
+        }
+        Command::CheckSources { query } => {
+            let results = preview_sources(query.as_deref().unwrap_or_default()).await;
+            if results.is_empty() {
+                bail!("no sources match query");
+            }
+            for (name, res) in results {
+                match res {
+                    Ok(updates) => {
+                        println!("{name}: {} updates", updates.len());
+                        for u in updates {
+                            println!("  {} at {}", u.app_id(), DateTime::format(u.update_time()));
+                            if let Some(link) = u.update_link() {
+                                println!("    {link}");
+                            }
+                        }
+                    }
+                    Err(e) => println!("{name}: failed: {e}"),
+                }
+            }
+        }
+        Command::DbBackup { path } => {
+            if path.exists() {
+                bail!("{} already exists", path.display());
+            }
+            // backup is made before upgrade, so database is not migrated
+            let db = config.db.open_existing().await?;
+            db.backup(&path.to_string_lossy()).await?;
+            db.close().await;
+            println!("database is copied to {}", path.display());
+        }
+        Command::DbRestore { path } => {
+            if config.db.backend != DbBackend::Sqlite {
+                bail!("restore is only supported with sqlite");
+            }
+            let version =
+                db::DB::restore(&config.db.sqlite_path(), &path.to_string_lossy()).await?;
+            let db = config.db.open().await?;
+            let migrated = db.migration_version().await?;
+            db.close().await;
+            println!(
+                "database is restored from {}, version {version}, migrated to {migrated}",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Open database, which is migrated by bot of this version. Commands don't
+/// migrate it, as it can be used by running bot of other version
+async fn open_db(config: &Config) -> Result<DB> {
+    let db = config.db.open_existing().await?;
+    db.check_version().await?;
+    Ok(db)
+}
+
+/// Only warnings and errors are logged, to stderr, so output of commands
+/// stays clean
+fn init_logger() {
+    use simplelog::*;
+
+    let mut config = ConfigBuilder::new();
+    for target in NOISY_LOG_TARGETS {
+        config.add_filter_ignore_str(target);
+    }
+    TermLogger::init(
+        LevelFilter::Warn,
+        config.build(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    )
+    .expect("failed to init logger");
+}
+
+fn user_csv(user: &User) -> String {
+    [
+        user.user_id().to_string(),
+        csv_field(user.username().unwrap_or_default()),
+        csv_field(user.name().unwrap_or_default()),
+        csv_field(user.lang()),
+        DateTime::format(user.joined_at()),
+        user.bot_blocked().to_string(),
+        user.banned().to_string(),
+    ]
+    .join(",")
+}
+
+/// Quote field, if it contains separators or quotes
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        for (i, (s, expected)) in [
+            ("", ""),
+            ("name", "name"),
+            ("a, b", "\"a, b\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_eq!(csv_field(s), expected, "test table[{i}]");
+        }
+    }
+
+    #[test]
+    fn test_parse_cli() {
+        for (i, (args, expected)) in [
+            (vec![], "None"),
+            (vec!["run"], "Some(Run)"),
+            (
+                vec!["migrate", "--revert-to", "12"],
+                "Some(Migrate { revert_to: Some(12) })",
+            ),
+            (
+                vec!["broadcast", "--file", "msg.txt"],
+                "Some(Broadcast { file: \"msg.txt\", admin: None })",
+            ),
+            (
+                vec!["check-sources", "channel"],
+                "Some(CheckSources { query: Some(\"channel\") })",
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let cli = Cli::try_parse_from(["app-pulse-bot"].into_iter().chain(args)).unwrap();
+            assert_eq!(format!("{:?}", cli.command), expected, "test table[{i}]");
+        }
+    }
+}
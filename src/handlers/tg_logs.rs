@@ -1,31 +1,102 @@
-use std::fmt::Display;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::Result;
-use common::LogError;
+use common::{tg_len, tg_truncate, LogError};
 use log::Level;
 use teloxide::{
     payloads::SendMessageSetters,
     requests::Requester,
-    types::{ChatId, ParseMode},
-    utils::markdown::code_block_with_lang,
+    types::{ChatId, MessageId, ParseMode, ThreadId},
+    utils::markdown::{escape, escape_code},
     Bot,
 };
 use time::format_description::well_known::Rfc3339;
-use tokio::sync::mpsc::Receiver;
+use tokio::{
+    sync::mpsc::Receiver,
+    time::{interval, Instant, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Logs are collected and sent once in this interval
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+/// Repeats of sent log are not sent during this window, only their count is
+/// sent at its end
+const DIGEST_WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Telegram's limit of message length, in UTF-16 code units
+const MAX_MESSAGE_LEN: usize = 4096;
+const CODE_BLOCK_START: &str = "```log\n";
+const CODE_BLOCK_END: &str = "\n```";
+/// Logs, which don't fit into this count of messages, are skipped
+const MAX_MESSAGES_PER_FLUSH: usize = 3;
 
-pub(crate) async fn start_tg_logs_job(bot: Bot, chat_id: ChatId, mut rx: Receiver<LogMessage>) {
-    while let Some(text) = rx.recv().await {
-        bot.send_message(chat_id, text.to_string())
-            .parse_mode(ParseMode::MarkdownV2)
-            .await
-            .log_error_msg("failed to send log");
+/// Send logs to their chats in batches, until `token` is cancelled. Then
+/// logs, which are already queued, are flushed
+pub(crate) async fn start_tg_logs_job(
+    bot: Bot,
+    mut rx: Receiver<LogRecord>,
+    token: CancellationToken,
+) {
+    let mut batches: HashMap<Destination, LogBatch> = HashMap::new();
+    let mut interval = interval(FLUSH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            record = rx.recv() => match record {
+                Some(r) => batches.entry(r.dest).or_default().push(r.msg, Instant::now()),
+                None => break,
+            },
+            _ = interval.tick() => {
+                for (dest, batch) in &mut batches {
+                    send_logs(&bot, *dest, batch.flush(Instant::now())).await;
+                }
+            }
+            _ = token.cancelled() => break,
+        }
+    }
+    while let Ok(r) = rx.try_recv() {
+        batches
+            .entry(r.dest)
+            .or_default()
+            .push(r.msg, Instant::now());
+    }
+    for (dest, batch) in &mut batches {
+        // report all repeats, without waiting for end of digest window
+        send_logs(&bot, *dest, batch.flush(Instant::now() + DIGEST_WINDOW)).await;
     }
 }
 
-#[derive(Debug)]
+async fn send_logs(bot: &Bot, dest: Destination, texts: Vec<String>) {
+    for text in texts {
+        let mut req = bot
+            .send_message(ChatId(dest.chat_id), text)
+            .parse_mode(ParseMode::MarkdownV2);
+        if let Some(topic_id) = dest.topic_id {
+            req = req.message_thread_id(ThreadId(MessageId(topic_id)));
+        }
+        req.await.log_error_msg("failed to send log");
+    }
+}
+
+/// Chat, and optionally forum topic in it, where logs are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Destination {
+    pub(crate) chat_id: i64,
+    pub(crate) topic_id: Option<i32>,
+}
+
+/// Log with its destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LogRecord {
+    pub(crate) dest: Destination,
+    pub(crate) msg: LogMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum LogMessage {
     Code(String),
     Markdown(String),
+    /// Count of logs, dropped because queue was full
+    Dropped(usize),
 }
 
 impl LogMessage {
@@ -35,11 +106,7 @@ impl LogMessage {
         file: Option<&str>,
         line: Option<u32>,
     ) -> Self {
-        let time = match time_now() {
-            Ok(t) => t,
-            Err(e) => format!("failed to format time: {e}\n"),
-        };
-        let mut msg = format!("{time} [ERROR] {}\n        at {target}", s.into());
+        let mut msg = format!("[ERROR] {}\n        at {target}", s.into());
         if let Some(file) = file {
             msg += &format!(": {file}");
             if let Some(line) = line {
@@ -56,15 +123,167 @@ impl LogMessage {
     pub(crate) fn simple_with_level(s: impl Into<String>, level: Level) -> Self {
         Self::Markdown(format!("{}: {}", level_to_string(level), s.into()))
     }
-}
 
-impl Display for LogMessage {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// Render for sending with markdown. `time` is added to code logs, and
+    /// `repeats` is note about count of repeats
+    fn render(&self, time: Option<&str>, repeats: Option<String>) -> String {
         match self {
-            LogMessage::Code(s) => code_block_with_lang(s, "log").fmt(f),
-            LogMessage::Markdown(s) => s.fmt(f),
+            LogMessage::Code(s) => {
+                let mut text = match time {
+                    Some(time) => format!("{time} {s}"),
+                    None => s.clone(),
+                };
+                if let Some(repeats) = repeats {
+                    text = format!("{text}\n{repeats}");
+                }
+                let max = MAX_MESSAGE_LEN - CODE_BLOCK_START.len() - CODE_BLOCK_END.len();
+                let text = truncate_escaped(&escape_code(&text), max);
+                format!("{CODE_BLOCK_START}{text}{CODE_BLOCK_END}")
+            }
+            LogMessage::Markdown(s) => {
+                let text = match repeats {
+                    Some(repeats) => format!("{s} {}", escape(&repeats)),
+                    None => s.clone(),
+                };
+                truncate_escaped(&text, MAX_MESSAGE_LEN)
+            }
+            LogMessage::Dropped(count) => escape(&format!(
+                "Warning: {count} logs are dropped, because log queue is full"
+            )),
+        }
+    }
+}
+
+/// Log, waiting for sending
+#[derive(Debug)]
+struct Entry {
+    msg: LogMessage,
+    /// Time of first occurrence
+    time: String,
+    count: usize,
+}
+
+/// Repeats of sent log
+#[derive(Debug)]
+struct Repeats {
+    msg: LogMessage,
+    since: Instant,
+    count: usize,
+}
+
+/// Logs, collected between sending. Identical logs are sent once, with count
+/// of repeats
+#[derive(Debug, Default)]
+struct LogBatch {
+    pending: Vec<Entry>,
+    /// Logs, sent during last [`DIGEST_WINDOW`]
+    sent: Vec<Repeats>,
+    dropped: usize,
+}
+
+impl LogBatch {
+    fn push(&mut self, msg: LogMessage, now: Instant) {
+        if let LogMessage::Dropped(count) = msg {
+            self.dropped += count;
+            return;
+        }
+        if let Some(r) = self
+            .sent
+            .iter_mut()
+            .find(|r| r.msg == msg && now.duration_since(r.since) < DIGEST_WINDOW)
+        {
+            r.count += 1;
+            return;
+        }
+        if let Some(e) = self.pending.iter_mut().find(|e| e.msg == msg) {
+            e.count += 1;
+            return;
+        }
+        let time = match time_now() {
+            Ok(t) => t,
+            Err(e) => format!("failed to format time: {e}\n"),
+        };
+        self.pending.push(Entry {
+            msg,
+            time,
+            count: 1,
+        });
+    }
+
+    /// Take messages to send: pending logs, counts of repeats, which digest
+    /// window is ended, and count of dropped logs
+    fn flush(&mut self, now: Instant) -> Vec<String> {
+        let mut texts = vec![];
+
+        let window_mins = DIGEST_WINDOW.as_secs() / 60;
+        let (ended, sent) = std::mem::take(&mut self.sent)
+            .into_iter()
+            .partition::<Vec<_>, _>(|r| now.duration_since(r.since) >= DIGEST_WINDOW);
+        self.sent = sent;
+        for r in ended.into_iter().filter(|r| r.count > 0) {
+            let repeats = format!("×{} in last {window_mins} min", r.count);
+            texts.push(r.msg.render(None, Some(repeats)));
+        }
+
+        for e in std::mem::take(&mut self.pending) {
+            let repeats = (e.count > 1).then(|| format!("×{}", e.count));
+            texts.push(e.msg.render(Some(&e.time), repeats));
+            self.sent.push(Repeats {
+                msg: e.msg,
+                since: now,
+                count: 0,
+            });
+        }
+
+        if self.dropped > 0 {
+            texts.push(LogMessage::Dropped(self.dropped).render(None, None));
+            self.dropped = 0;
         }
+
+        join_messages(texts)
+    }
+}
+
+/// Join texts into messages, which fit into telegram's limit. Texts, which
+/// don't fit into [`MAX_MESSAGES_PER_FLUSH`] messages, are skipped
+fn join_messages(texts: Vec<String>) -> Vec<String> {
+    let mut messages: Vec<String> = vec![];
+    let mut skipped = 0;
+    for text in texts {
+        let fits = messages
+            .last()
+            .is_some_and(|last| tg_len(last) + tg_len(&text) + 2 <= MAX_MESSAGE_LEN);
+        if fits {
+            let last = messages.last_mut().expect("checked above");
+            last.push_str("\n\n");
+            last.push_str(&text);
+        } else if messages.len() < MAX_MESSAGES_PER_FLUSH {
+            messages.push(text);
+        } else {
+            skipped += 1;
+        }
+    }
+    if skipped > 0 {
+        messages.push(escape(&format!(
+            "Warning: {skipped} logs are skipped, because there are too many"
+        )));
+    }
+    messages
+}
+
+/// Truncate escaped markdown to `max` UTF-16 code units, which telegram
+/// counts. Escape sequence at end is not split
+fn truncate_escaped(s: &str, max: usize) -> String {
+    if tg_len(s) <= max {
+        return s.to_string();
     }
+    let mut text = tg_truncate(s, max - 1).to_string();
+    // lone backslash would escape ellipsis
+    if text.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1 {
+        text.pop();
+    }
+    text.push('…');
+    text
 }
 
 fn level_to_string(level: Level) -> String {
@@ -82,3 +301,114 @@ fn time_now() -> Result<String> {
     let time = time::OffsetDateTime::now_utc();
     Ok(time.format(&Rfc3339)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_coalesce() {
+        let now = Instant::now();
+        let mut batch = LogBatch::default();
+        for _ in 0..3 {
+            batch.push(LogMessage::simple("a"), now);
+        }
+        batch.push(LogMessage::simple("b"), now);
+        assert_eq!(batch.flush(now), vec!["a ×3\n\nb"]);
+
+        // repeats are counted until end of digest window
+        batch.push(LogMessage::simple("a"), now + FLUSH_INTERVAL);
+        batch.push(LogMessage::simple("a"), now + FLUSH_INTERVAL);
+        assert!(batch.flush(now + FLUSH_INTERVAL).is_empty());
+        assert_eq!(batch.flush(now + DIGEST_WINDOW), vec!["a ×2 in last 5 min"]);
+
+        // after window log is sent again
+        batch.push(LogMessage::simple("a"), now + DIGEST_WINDOW);
+        assert_eq!(batch.flush(now + DIGEST_WINDOW), vec!["a"]);
+    }
+
+    #[test]
+    fn test_batch_dropped() {
+        let now = Instant::now();
+        let mut batch = LogBatch::default();
+        batch.push(LogMessage::Dropped(2), now);
+        batch.push(LogMessage::Dropped(3), now);
+        assert_eq!(
+            batch.flush(now),
+            vec![escape(
+                "Warning: 5 logs are dropped, because log queue is full"
+            )]
+        );
+        assert!(batch.flush(now).is_empty());
+    }
+
+    #[test]
+    fn test_join_messages() {
+        let long = "a".repeat(MAX_MESSAGE_LEN - 10);
+        let emoji = "😀".repeat(MAX_MESSAGE_LEN / 4);
+        for (i, (texts, expected)) in [
+            (vec![], vec![]),
+            (vec!["a", "b"], vec!["a\n\nb".to_string()]),
+            (
+                vec![&long, "b", &long],
+                vec![long.clone() + "\n\nb", long.clone()],
+            ),
+            // emoji take 2 UTF-16 code units
+            (
+                vec![&emoji, &emoji, "b"],
+                vec![emoji.clone(), emoji.clone() + "\n\nb"],
+            ),
+            (
+                vec![&long, &long, &long, &long, &long],
+                vec![
+                    long.clone(),
+                    long.clone(),
+                    long.clone(),
+                    escape("Warning: 2 logs are skipped, because there are too many"),
+                ],
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let texts = texts.into_iter().map(String::from).collect();
+            assert_eq!(join_messages(texts), expected, "test table[{i}]");
+        }
+    }
+
+    #[test]
+    fn test_truncate_escaped() {
+        for (i, (s, max, expected)) in [
+            ("abc", 3, "abc"),
+            ("abcdef", 5, "abcd…"),
+            ("бббббб", 5, "бббб…"),
+            (r"ab\.cd", 5, r"ab\.…"),
+            (r"abc\.d", 5, "abc…"),
+            (r"ab\\\.d", 5, r"ab\\…"),
+            ("😀😀😀", 5, "😀😀…"),
+            ("😀😀😀", 4, "😀…"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_eq!(truncate_escaped(s, max), expected, "test table[{i}]");
+        }
+    }
+
+    #[test]
+    fn test_render_long() {
+        let long = "б`".repeat(MAX_MESSAGE_LEN);
+        for (i, msg) in [
+            LogMessage::log_error(&long, "target", None, None),
+            LogMessage::simple(escape(&long)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let text = msg.render(Some("time"), Some("×2".to_string()));
+            assert_eq!(tg_len(&text), MAX_MESSAGE_LEN, "test table[{i}]");
+        }
+        let text = LogMessage::log_error(&long, "target", None, None).render(None, None);
+        assert!(text.starts_with(CODE_BLOCK_START) && text.ends_with(CODE_BLOCK_END));
+    }
+}
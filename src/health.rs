@@ -0,0 +1,342 @@
+//! Health checks of bot, used by HTTP server and systemd watchdog
+
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
+use sd_notify::NotifyState;
+use teloxide::{
+    stop::StopToken,
+    types::AllowedUpdate,
+    update_listeners::{AsUpdateStream, UpdateListener},
+};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use common::{DateTime, Heartbeat, LogError, UnixDateTime};
+use db::{SourceRepo, DB};
+use sources::SourcesControl;
+
+/// Source, which was not fetched successfully for this long, makes bot not
+/// ready
+const SOURCE_READY_MAX_AGE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Dispatcher beats, while it waits for updates from listener
+const DISPATCHER_BEAT_PERIOD: Duration = Duration::from_secs(60);
+/// Dispatcher is considered stalled, if it didn't wait for updates for this
+/// long, e.g. all handlers are stuck
+const DISPATCHER_STALL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// State of dispatcher, reported by bot
+#[derive(Debug, Clone)]
+pub(crate) struct DispatcherStatus {
+    running: Arc<AtomicBool>,
+    last_update_at: Arc<AtomicI64>,
+    heartbeat: Heartbeat,
+}
+
+impl Default for DispatcherStatus {
+    fn default() -> Self {
+        Self {
+            running: Arc::default(),
+            last_update_at: Arc::default(),
+            heartbeat: Heartbeat::new(DISPATCHER_STALL_TIMEOUT),
+        }
+    }
+}
+
+impl DispatcherStatus {
+    pub(crate) fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::Relaxed);
+    }
+    /// Report that update from telegram is received
+    pub(crate) fn on_update(&self) {
+        self.last_update_at
+            .store(DateTime::now(), Ordering::Relaxed);
+    }
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+    /// `None`, if no updates were received yet
+    fn last_update_at(&self) -> Option<UnixDateTime> {
+        Some(self.last_update_at.load(Ordering::Relaxed)).filter(|&t| t > 0)
+    }
+    /// Wrap listener of dispatcher, so dispatcher beats, while it polls
+    /// listener for updates
+    pub(crate) fn listener<L: UpdateListener>(&self, listener: L) -> HeartbeatListener<L> {
+        HeartbeatListener {
+            inner: listener,
+            heartbeat: self.heartbeat.clone(),
+        }
+    }
+    /// Dispatcher is running and not stalled at `now`
+    fn check(&self, now: UnixDateTime) -> Result<String, String> {
+        if !self.is_running() {
+            return Err("not running".to_string());
+        }
+        if self.heartbeat.is_stalled_at(now) {
+            return Err("stalled".to_string());
+        }
+        Ok(match self.last_update_at() {
+            Some(t) => format!("last update at {}", DateTime::format(t)),
+            None => "no updates yet".to_string(),
+        })
+    }
+}
+
+/// Update listener, which beats [`DispatcherStatus`] heartbeat. See
+/// [`DispatcherStatus::listener`]
+pub(crate) struct HeartbeatListener<L> {
+    inner: L,
+    heartbeat: Heartbeat,
+}
+
+impl<L: UpdateListener> UpdateListener for HeartbeatListener<L> {
+    type Err = L::Err;
+
+    fn stop_token(&mut self) -> StopToken {
+        self.inner.stop_token()
+    }
+    fn hint_allowed_updates(&mut self, hint: &mut dyn Iterator<Item = AllowedUpdate>) {
+        self.inner.hint_allowed_updates(hint)
+    }
+}
+
+impl<'a, L: UpdateListener> AsUpdateStream<'a> for HeartbeatListener<L> {
+    type StreamErr = L::Err;
+    type Stream = HeartbeatStream<<L as AsUpdateStream<'a>>::Stream>;
+
+    fn as_stream(&'a mut self) -> Self::Stream {
+        HeartbeatStream::new(self.inner.as_stream(), self.heartbeat.clone())
+    }
+}
+
+/// Stream of updates, which beats, when it's polled. Timer wakes dispatcher
+/// every [`DISPATCHER_BEAT_PERIOD`], even if there are no updates
+pub(crate) struct HeartbeatStream<S> {
+    inner: Pin<Box<S>>,
+    heartbeat: Heartbeat,
+    interval: Interval,
+}
+
+impl<S> HeartbeatStream<S> {
+    fn new(inner: S, heartbeat: Heartbeat) -> Self {
+        let mut interval = time::interval(DISPATCHER_BEAT_PERIOD);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            inner: Box::pin(inner),
+            heartbeat,
+            interval,
+        }
+    }
+}
+
+impl<S: Stream> Stream for HeartbeatStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        this.heartbeat.beat(DISPATCHER_STALL_TIMEOUT);
+        // schedule next wake up
+        while this.interval.poll_tick(cx).is_ready() {}
+        this.inner.as_mut().poll_next(cx)
+    }
+}
+
+/// Results of health checks
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    checks: Vec<(String, Result<String, String>)>,
+}
+
+impl Report {
+    fn check(&mut self, name: impl Into<String>, result: Result<String, String>) {
+        self.checks.push((name.into(), result));
+    }
+    pub(crate) fn is_ok(&self) -> bool {
+        self.checks.iter().all(|(_, r)| r.is_ok())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, result) in &self.checks {
+            let (status, details) = match result {
+                Ok(s) => ("ok", s),
+                Err(s) => ("FAIL", s),
+            };
+            if details.is_empty() {
+                writeln!(f, "{name}: {status}")?;
+            } else {
+                writeln!(f, "{name}: {status}, {details}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Health {
+    db: DB,
+    sources: SourcesControl,
+    dispatcher: DispatcherStatus,
+}
+
+impl Health {
+    pub(crate) fn new(db: DB, sources: SourcesControl, dispatcher: DispatcherStatus) -> Self {
+        Self {
+            db,
+            sources,
+            dispatcher,
+        }
+    }
+
+    /// Bot is alive: dispatcher is running and waits for updates, and no update
+    /// loop is stalled.
+    /// Not alive bot should be restarted
+    pub(crate) fn live(&self) -> Report {
+        let mut report = Report::default();
+        self.check_dispatcher(&mut report);
+        let stalled = self.sources.stalled();
+        report.check(
+            "sources",
+            if stalled.is_empty() {
+                Ok(format!("{} running", self.sources.names().len()))
+            } else {
+                Err(format!("stalled: {}", stalled.join(", ")))
+            },
+        );
+        report
+    }
+
+    /// Bot is alive, DB is available and every running source was fetched
+    /// recently
+    pub(crate) async fn ready(&self) -> Report {
+        let mut report = self.live();
+        report.check(
+            "db",
+            self.db
+                .ping()
+                .await
+                .map(|_| String::new())
+                .map_err(|e| e.to_string()),
+        );
+        self.check_sources_fetched(&mut report).await;
+        report
+    }
+
+    fn check_dispatcher(&self, report: &mut Report) {
+        report.check("dispatcher", self.dispatcher.check(DateTime::now()));
+    }
+
+    async fn check_sources_fetched(&self, report: &mut Report) {
+        let health = match self.db.select_sources_health().await {
+            Ok(h) => h,
+            Err(e) => {
+                report.check("sources fetch", Err(e.to_string()));
+                return;
+            }
+        };
+        let min_success_at = DateTime::now() - SOURCE_READY_MAX_AGE.as_secs() as i64;
+        for name in self.sources.names() {
+            let last_success_at = health
+                .iter()
+                .find(|h| h.source().name() == name)
+                .map_or(0, |h| h.last_success_at());
+            report.check(
+                format!("source {name}"),
+                if last_success_at >= min_success_at {
+                    Ok(format!("fetched at {}", DateTime::format(last_success_at)))
+                } else if last_success_at == 0 {
+                    Err("never fetched".to_string())
+                } else {
+                    Err(format!("fetched at {}", DateTime::format(last_success_at)))
+                },
+            );
+        }
+    }
+}
+
+/// Notify systemd, that bot is started, and ping watchdog while bot is
+/// alive. Does nothing, if bot is not started by systemd
+pub(crate) async fn start_systemd_notify_job(health: Health) {
+    sd_notify::notify(false, &[NotifyState::Ready]).log_error_msg("failed to notify systemd");
+
+    let mut usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut usec) {
+        log::debug!("systemd watchdog is disabled");
+        return;
+    }
+    let period = Duration::from_micros(usec) / 2;
+    log::debug!("pinging systemd watchdog every {period:?}");
+    let mut interval = time::interval(period);
+    loop {
+        interval.tick().await;
+        let report = health.live();
+        if report.is_ok() {
+            sd_notify::notify(false, &[NotifyState::Watchdog])
+                .log_error_msg("failed to ping systemd watchdog");
+        } else {
+            log::error!("bot is not alive, skip pinging systemd watchdog:\n{report}");
+        }
+    }
+}
+
+/// Notify systemd, that bot is stopping
+pub(crate) fn notify_systemd_stopping() {
+    sd_notify::notify(false, &[NotifyState::Stopping]).log_error_msg("failed to notify systemd");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        report.check("a", Ok(String::new()));
+        assert!(report.is_ok());
+        report.check("b", Err("stalled".to_string()));
+        assert!(!report.is_ok());
+        assert_eq!(report.to_string(), "a: ok\nb: FAIL, stalled\n");
+    }
+
+    /// Stream, which never has updates
+    struct Idle;
+
+    impl Stream for Idle {
+        type Item = ();
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<()>> {
+            Poll::Pending
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_heartbeat() {
+        let status = DispatcherStatus::default();
+        let now = DateTime::now();
+        assert_eq!(status.check(now), Err("not running".to_string()));
+
+        status.set_running(true);
+        assert!(status.check(now).is_ok());
+        // dispatcher didn't poll listener
+        let stalled_at = now + DISPATCHER_STALL_TIMEOUT.as_secs() as i64 + 1;
+        assert_eq!(status.check(stalled_at), Err("stalled".to_string()));
+
+        // dispatcher waits for updates, and beats
+        status.heartbeat.beat(Duration::ZERO);
+        let mut stream = HeartbeatStream::new(Idle, status.heartbeat.clone());
+        let polled = std::future::poll_fn(|cx| Poll::Ready(Pin::new(&mut stream).poll_next(cx)));
+        assert!(polled.await.is_pending());
+        assert!(status.check(now + 60).is_ok());
+        assert!(status.check(stalled_at + 60).is_err());
+    }
+}
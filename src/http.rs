@@ -0,0 +1,148 @@
+//! HTTP server for monitoring
+
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tokio::{net::TcpListener, sync::mpsc::Sender};
+
+use common::LogError;
+use db::DB;
+
+use crate::health::Health;
+
+/// Buckets for DB queries duration, in seconds
+const DB_QUERY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Returns number of messages, waiting in queue. `None`, if queue is closed
+type QueueDepth = Box<dyn Fn() -> Option<usize> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct HttpState {
+    metrics: PrometheusHandle,
+    db: DB,
+    queues: Arc<Vec<(&'static str, QueueDepth)>>,
+}
+
+impl HttpState {
+    /// Install metrics recorder. Should be called once, before any metrics
+    /// are recorded
+    pub(crate) fn new(db: DB) -> Result<Self> {
+        let metrics = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full("db_query_duration_seconds".to_string()),
+                DB_QUERY_BUCKETS,
+            )?
+            .install_recorder()?;
+        describe_metrics();
+        Ok(Self {
+            metrics,
+            db,
+            queues: Arc::default(),
+        })
+    }
+    /// Report depth of queue in metrics. Queue is not kept open by this
+    pub(crate) fn with_queue<T: Send + 'static>(
+        mut self,
+        name: &'static str,
+        tx: &Sender<T>,
+    ) -> Self {
+        let tx = tx.downgrade();
+        let depth: QueueDepth =
+            Box::new(move || tx.upgrade().map(|tx| tx.max_capacity() - tx.capacity()));
+        Arc::get_mut(&mut self.queues)
+            .expect("queues are added before server is started")
+            .push((name, depth));
+        self
+    }
+}
+
+fn describe_metrics() {
+    use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+    describe_counter!(
+        "source_updates_fetched_total",
+        "New updates, found when fetching sources"
+    );
+    describe_counter!("source_fetch_errors_total", "Failed fetches of sources");
+    describe_counter!(
+        "notifications_total",
+        "Notifications sent to users, by status"
+    );
+    describe_counter!("callback_queries_total", "Callback queries, by type");
+    describe_histogram!(
+        "db_query_duration_seconds",
+        Unit::Seconds,
+        "Duration of DB queries, by method of DB"
+    );
+    describe_gauge!("queue_depth", "Messages, waiting in queue");
+    describe_gauge!("users", "Users, by state");
+}
+
+pub(crate) async fn start_http_server(address: SocketAddr, state: HttpState, health: Health) {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+        .merge(
+            Router::new()
+                .route("/health/live", get(live_handler))
+                .route("/health/ready", get(ready_handler))
+                .with_state(health),
+        );
+
+    let listener = match TcpListener::bind(address).await {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!("failed to bind http server to {address}: {e}");
+            return;
+        }
+    };
+    log::debug!("http server listening on {address}");
+    axum::serve(listener, app)
+        .await
+        .log_error_msg("http server failed");
+}
+
+async fn metrics_handler(State(state): State<HttpState>) -> (StatusCode, String) {
+    for (name, depth) in state.queues.iter() {
+        if let Some(depth) = depth() {
+            metrics::gauge!("queue_depth", "queue" => *name).set(depth as f64);
+        }
+    }
+    match state.db.load_user_states().await {
+        Ok(users) => {
+            for (user_state, count) in [
+                ("active", users.active),
+                ("blocked", users.blocked),
+                ("banned", users.banned),
+            ] {
+                metrics::gauge!("users", "state" => user_state).set(count);
+            }
+        }
+        Err(e) => log::error!("failed to load user states for metrics: {e}"),
+    }
+
+    state.metrics.run_upkeep();
+    (StatusCode::OK, state.metrics.render())
+}
+
+/// Bot should be restarted, if this check fails
+async fn live_handler(State(health): State<Health>) -> (StatusCode, String) {
+    let report = health.live();
+    (report_status(report.is_ok()), report.to_string())
+}
+
+/// Bot is alive and able to serve users
+async fn ready_handler(State(health): State<Health>) -> (StatusCode, String) {
+    let report = health.ready().await;
+    (report_status(report.is_ok()), report.to_string())
+}
+
+fn report_status(ok: bool) -> StatusCode {
+    if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
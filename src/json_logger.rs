@@ -0,0 +1,174 @@
+//! Logger, writing JSON lines for log collectors
+
+use std::{
+    fs::OpenOptions,
+    io::{self, LineWriter, Write},
+    sync::Mutex,
+};
+
+use log::{
+    kv::{self, Key, VisitSource},
+    LevelFilter, Metadata, Record,
+};
+use serde_json::{Map, Value};
+use simplelog::SharedLogger;
+use time::format_description::well_known::Rfc3339;
+
+/// Fields, written for every log. Key-values with same keys are written with
+/// `kv_` prefix
+const FIELDS: &[&str] = &["ts", "level", "target", "file", "line", "msg"];
+
+/// Writes each log as JSON object on its own line, with all key-values of log
+/// as fields
+pub(crate) struct JsonLogger {
+    level: LevelFilter,
+    /// Logs from targets, starting with any of these, are skipped
+    ignore: &'static [&'static str],
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLogger {
+    /// Write to file at `path`, or to stdout if not set
+    pub(crate) fn new(
+        level: LevelFilter,
+        ignore: &'static [&'static str],
+        path: Option<&str>,
+    ) -> io::Result<Box<Self>> {
+        let out: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(LineWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => Box::new(io::stdout()),
+        };
+        Ok(Box::new(Self {
+            level,
+            ignore,
+            out: Mutex::new(out),
+        }))
+    }
+}
+
+impl log::Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+            && !self.ignore.iter().any(|s| metadata.target().starts_with(s))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let time = time::OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default();
+        let mut line = to_json(record, &time).to_string();
+        line.push('\n');
+        if let Ok(mut out) = self.out.lock() {
+            // there is nowhere to report failed logging
+            let _ = out.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.flush();
+        }
+    }
+}
+
+impl SharedLogger for JsonLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn log::Log> {
+        Box::new(*self)
+    }
+}
+
+fn to_json(record: &Record, time: &str) -> Value {
+    let mut fields = Map::new();
+    fields.insert("ts".into(), time.into());
+    fields.insert("level".into(), record.level().as_str().into());
+    fields.insert("target".into(), record.target().into());
+    if let Some(file) = record.file() {
+        fields.insert("file".into(), file.into());
+    }
+    if let Some(line) = record.line() {
+        fields.insert("line".into(), line.into());
+    }
+    fields.insert("msg".into(), record.args().to_string().into());
+    // collecting to map never fails
+    let _ = record.key_values().visit(&mut KeyValues(&mut fields));
+    Value::Object(fields)
+}
+
+struct KeyValues<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let key = if FIELDS.contains(&key.as_str()) {
+            format!("kv_{key}")
+        } else {
+            key.to_string()
+        };
+        let value = if let Some(v) = value.to_bool() {
+            v.into()
+        } else if let Some(v) = value.to_i64() {
+            v.into()
+        } else if let Some(v) = value.to_u64() {
+            v.into()
+        } else if let Some(v) = value.to_f64() {
+            v.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let kv: &[(&str, &dyn kv::ToValue)] = &[
+            ("tg", &true),
+            ("user_id", &-100_i64),
+            ("app_id", &"com.example"),
+            ("msg", &"kv"),
+        ];
+        let record = Record::builder()
+            .level(Level::Info)
+            .target("app::module")
+            .file(Some("src/module.rs"))
+            .line(Some(10))
+            .key_values(&kv)
+            .args(format_args!("hello"))
+            .build();
+        assert_eq!(
+            to_json(&record, "2025-01-01T00:00:00Z"),
+            json!({
+                "ts": "2025-01-01T00:00:00Z",
+                "level": "INFO",
+                "target": "app::module",
+                "file": "src/module.rs",
+                "line": 10,
+                "msg": "hello",
+                "tg": true,
+                "user_id": -100,
+                "app_id": "com.example",
+                "kv_msg": "kv",
+            })
+        );
+    }
+}
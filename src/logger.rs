@@ -1,13 +1,25 @@
-use std::{convert::identity, thread};
+use std::{
+    collections::HashMap,
+    convert::identity,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock, RwLockReadGuard,
+    },
+};
 
-use log::{kv::Key, Level, Metadata, Record};
+use log::{kv::Key, Level, LevelFilter, Metadata, Record};
+use serde::Deserialize;
 use simplelog::SharedLogger;
+use teloxide::utils::markdown::escape;
 use tokio::sync::mpsc::Sender;
 
-use crate::{handlers::tg_logs::LogMessage, TG_LOG_ENABLED};
+use crate::{
+    config::deserialize_parse,
+    handlers::tg_logs::{Destination, LogMessage, LogRecord},
+};
 
-/// By default only error logs are sent. If [`crate::TG_LOG_ENABLED`] is
-/// false, do not send anything
+/// By default only error logs are sent. Logger is used only when log chat is
+/// set in config
 ///
 /// If called with "tg" key like `log::info!(tg = true; "..")`, will also send
 /// WARN and INFO.
@@ -29,22 +41,70 @@ use crate::{handlers::tg_logs::LogMessage, TG_LOG_ENABLED};
 /// - All warn logs will contain `Warning: ` before message
 ///
 /// All debug and trace messages are filtered.
+///
+/// Logs, matching one of [`Route`]s, are sent to chat of first matching route
+/// instead, regardless of "tg". Text of such logs is escaped, unless "tg" is
+/// set or it's sent in code block.
+///
+/// Logging never blocks: when queue is full, message is dropped, and count of
+/// dropped messages is sent later
+///
+/// Config can be replaced while logger is running with [`TgLoggerHandle`]
 #[derive(Debug)]
 pub(crate) struct TgLogger {
-    sender: Sender<LogMessage>,
-    config: Config,
+    sender: Sender<LogRecord>,
+    config: Arc<RwLock<Config>>,
+    dropped: AtomicUsize,
+}
+
+/// Handle to replace config of installed [`TgLogger`]
+#[derive(Debug, Clone)]
+pub(crate) struct TgLoggerHandle(Arc<RwLock<Config>>);
+
+impl TgLoggerHandle {
+    pub(crate) fn set_config(&self, config: Config) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
 }
 
 impl TgLogger {
-    pub(crate) fn new(sx: Sender<LogMessage>, config: Config) -> Box<Self> {
-        let s = Self { sender: sx, config };
+    pub(crate) fn new(sx: Sender<LogRecord>, config: Config) -> Box<Self> {
+        let s = Self {
+            sender: sx,
+            config: Arc::new(RwLock::new(config)),
+            dropped: AtomicUsize::new(0),
+        };
         Box::new(s)
     }
+
+    pub(crate) fn handle(&self) -> TgLoggerHandle {
+        TgLoggerHandle(self.config.clone())
+    }
+
+    fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send(&self, dest: Destination, msg: LogMessage) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            let record = LogRecord {
+                dest: self.config().fallback().unwrap_or(dest),
+                msg: LogMessage::Dropped(dropped),
+            };
+            if self.sender.try_send(record).is_ok() {
+                self.dropped.fetch_sub(dropped, Ordering::Relaxed);
+            }
+        }
+        if self.sender.try_send(LogRecord { dest, msg }).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
 }
 
 impl log::Log for TgLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        TG_LOG_ENABLED && metadata.level() <= Level::Error
+        metadata.level() <= Level::Error
     }
 
     fn log(&self, record: &Record) {
@@ -64,33 +124,43 @@ impl log::Log for TgLogger {
             .and_then(|v| v.to_bool())
             .map_or_else(|| level <= Level::Error, identity);
 
-        if self.enabled(record.metadata()) || should_always_send {
-            let text = record.args().to_string();
-            if self.config.is_should_ignore(&text) {
-                return;
-            }
+        let config = self.config();
+        let route = config.routes.iter().find(|r| r.matches(record));
+        let routed = route.is_some();
+        let dest = match (route, config.chat) {
+            (Some(route), _) => route.destination(),
+            (None, Some(chat)) if self.enabled(record.metadata()) || should_always_send => chat,
+            _ => return,
+        };
 
-            let msg = if level <= Level::Error {
-                if wrap_in_code {
-                    LogMessage::log_error(text, record.target(), record.file(), record.line())
+        let text = record.args().to_string();
+        if config.is_should_ignore(&text) {
+            return;
+        }
+        // lock is taken again to send
+        drop(config);
+
+        let msg = match level {
+            // code blocks are escaped when rendered
+            Level::Error if wrap_in_code => {
+                LogMessage::log_error(text, record.target(), record.file(), record.line())
+            }
+            Level::Error | Level::Warn | Level::Info => {
+                // routed logs are not written with telegram's markdown in mind
+                let text = if routed && !should_always_send {
+                    escape(&text)
                 } else {
-                    LogMessage::simple_with_level(text, Level::Error)
-                }
-            } else if should_always_send {
-                match level {
-                    Level::Warn => LogMessage::simple_with_level(text, level),
-                    Level::Info => LogMessage::simple(text),
-                    _ => return,
+                    text
+                };
+                if level == Level::Info {
+                    LogMessage::simple(text)
+                } else {
+                    LogMessage::simple_with_level(text, level)
                 }
-            } else {
-                return;
-            };
-            thread::scope(|s| {
-                s.spawn(|| {
-                    let _ = self.sender.blocking_send(msg);
-                });
-            });
-        }
+            }
+            _ => return,
+        };
+        self.send(dest, msg);
     }
 
     fn flush(&self) {}
@@ -113,12 +183,71 @@ impl SharedLogger for TgLogger {
 #[derive(Debug, Default, Clone)]
 pub struct Config {
     ignore: Vec<String>,
+    /// Chat for logs, not matching any route
+    chat: Option<Destination>,
+    routes: Vec<Route>,
 }
 
 impl Config {
     fn is_should_ignore(&self, msg: &str) -> bool {
         self.ignore.iter().any(|pat| msg.contains(pat))
     }
+    /// Chat for reporting problems of logger itself
+    fn fallback(&self) -> Option<Destination> {
+        self.chat
+            .or_else(|| self.routes.first().map(Route::destination))
+    }
+}
+
+/// Rule to send logs to own chat or forum topic. Log should match all set
+/// conditions
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Route {
+    /// Least severe level, e.g. `warn` matches warn and error logs. Debug
+    /// and trace logs are never sent
+    #[serde(
+        default = "default_route_level",
+        deserialize_with = "deserialize_parse"
+    )]
+    level: LevelFilter,
+    /// Module, e.g. `sources` matches `sources` and `sources::extractor`
+    target: Option<String>,
+    /// Key-values of log, e.g. `{ event = "user_joined" }`
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    chat_id: i64,
+    /// Forum topic in chat
+    topic_id: Option<i32>,
+}
+
+fn default_route_level() -> LevelFilter {
+    LevelFilter::Info
+}
+
+impl Route {
+    fn matches(&self, record: &Record) -> bool {
+        record.level() <= self.level
+            && self.target.as_ref().is_none_or(|t| {
+                let target = record.target();
+                target == t
+                    || target
+                        .strip_prefix(t.as_str())
+                        .is_some_and(|s| s.starts_with("::"))
+            })
+            && self.tags.iter().all(|(key, value)| {
+                record
+                    .key_values()
+                    .get(Key::from_str(key))
+                    .is_some_and(|v| v.to_string() == *value)
+            })
+    }
+    fn destination(&self) -> Destination {
+        Destination {
+            chat_id: self.chat_id,
+            topic_id: self.topic_id,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +261,17 @@ impl ConfigBuilder {
         self.0.ignore.push(s.to_string());
         self
     }
+    pub fn chat(&mut self, chat_id: i64) -> &mut Self {
+        self.0.chat = Some(Destination {
+            chat_id,
+            topic_id: None,
+        });
+        self
+    }
+    pub fn add_route(&mut self, route: Route) -> &mut Self {
+        self.0.routes.push(route);
+        self
+    }
     pub fn build(&mut self) -> Config {
         self.0.clone()
     }
@@ -147,4 +287,164 @@ mod tests {
         assert!(conf.is_should_ignore("test - ignored"));
         assert!(!conf.is_should_ignore("not ignored"));
     }
+
+    #[test]
+    fn test_dropped() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let logger = TgLogger::new(tx, ConfigBuilder::new().chat(1).build());
+        let dest = Destination {
+            chat_id: 1,
+            topic_id: None,
+        };
+        let recv = |rx: &mut tokio::sync::mpsc::Receiver<LogRecord>| rx.try_recv().map(|r| r.msg);
+        logger.send(dest, LogMessage::simple("a"));
+        logger.send(dest, LogMessage::simple("b"));
+        logger.send(dest, LogMessage::simple("c"));
+        assert_eq!(recv(&mut rx), Ok(LogMessage::simple("a")));
+
+        logger.send(dest, LogMessage::simple("d"));
+        assert_eq!(recv(&mut rx), Ok(LogMessage::Dropped(2)));
+        assert!(rx.try_recv().is_err());
+
+        // "d" is dropped too
+        logger.send(dest, LogMessage::simple("e"));
+        assert_eq!(recv(&mut rx), Ok(LogMessage::Dropped(1)));
+    }
+
+    #[test]
+    fn test_route_matches() {
+        let route = |s: &str| toml::from_str::<Route>(&format!("chat_id = 1\n{s}")).unwrap();
+        let joined = [("event", "user_joined")];
+        let no_kv: [(&str, &str); 0] = [];
+        for (i, (route, level, target, kv, expected)) in [
+            (route(""), Level::Info, "app", &no_kv[..], true),
+            (route(""), Level::Debug, "app", &no_kv, false),
+            (route("level = 'warn'"), Level::Info, "app", &no_kv, false),
+            (route("level = 'warn'"), Level::Error, "app", &no_kv, true),
+            (
+                route("target = 'sources'"),
+                Level::Info,
+                "sources",
+                &no_kv,
+                true,
+            ),
+            (
+                route("target = 'sources'"),
+                Level::Info,
+                "sources::extractor",
+                &no_kv,
+                true,
+            ),
+            (
+                route("target = 'sources'"),
+                Level::Info,
+                "sources2",
+                &no_kv,
+                false,
+            ),
+            (
+                route("tags = { event = 'user_joined' }"),
+                Level::Info,
+                "app",
+                &joined,
+                true,
+            ),
+            (
+                route("tags = { event = 'user_left' }"),
+                Level::Info,
+                "app",
+                &joined,
+                false,
+            ),
+            (
+                route("tags = { event = 'user_joined' }"),
+                Level::Info,
+                "app",
+                &no_kv,
+                false,
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let record = Record::builder()
+                .level(level)
+                .target(target)
+                .key_values(&kv)
+                .build();
+            assert_eq!(route.matches(&record), expected, "test table[{i}]");
+        }
+    }
+
+    #[test]
+    fn test_set_config() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let logger = TgLogger::new(tx, ConfigBuilder::new().build());
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("a"))
+            .build();
+        log::Log::log(&*logger, &record);
+        assert!(rx.try_recv().is_err());
+
+        let route = toml::from_str("chat_id = 2").unwrap();
+        logger
+            .handle()
+            .set_config(ConfigBuilder::new().add_route(route).build());
+        log::Log::log(&*logger, &record);
+        let got = rx.try_recv().map(|r| (r.dest.chat_id, r.msg));
+        assert_eq!(got, Ok((2, LogMessage::simple("a"))));
+    }
+
+    #[test]
+    fn test_routing() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let route =
+            toml::from_str("chat_id = 2\ntopic_id = 3\ntags = { event = 'user_joined' }").unwrap();
+        let logger = TgLogger::new(tx, ConfigBuilder::new().chat(1).add_route(route).build());
+        let chat = Destination {
+            chat_id: 1,
+            topic_id: None,
+        };
+        let topic = Destination {
+            chat_id: 2,
+            topic_id: Some(3),
+        };
+        let joined = [("event", "user_joined")];
+        let tg = [("tg", true)];
+        let no_code = [("code", false)];
+        let no_kv: [(&str, &str); 0] = [];
+        for (i, (level, kv, expected)) in [
+            (
+                Level::Info,
+                &joined as &dyn log::kv::Source,
+                Some((topic, LogMessage::simple("a\\."))),
+            ),
+            (
+                Level::Error,
+                &joined,
+                Some((topic, LogMessage::log_error("a.", "", None, None))),
+            ),
+            (Level::Info, &tg, Some((chat, LogMessage::simple("a.")))),
+            (Level::Info, &no_kv, None),
+            (Level::Warn, &no_kv, None),
+            (
+                Level::Error,
+                &no_code,
+                Some((chat, LogMessage::simple_with_level("a.", Level::Error))),
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let record = Record::builder()
+                .level(level)
+                .key_values(kv)
+                .args(format_args!("a."))
+                .build();
+            log::Log::log(&*logger, &record);
+            let got = rx.try_recv().ok().map(|r| (r.dest, r.msg));
+            assert_eq!(got, expected, "test table[{i}]");
+        }
+    }
 }
@@ -1,111 +1,279 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
-use anyhow::Result;
-use dotenvy_macro::dotenv;
+use anyhow::{Context, Result};
+use clap::Parser;
 use reqwest::Client;
-use simplelog::LevelFilter;
-use teloxide::{prelude::*, types::BotCommandScope};
+use teloxide::{
+    dptree::di::DependencyMap,
+    prelude::*,
+    types::{BotCommandScope, Recipient},
+    update_listeners::{self, webhooks},
+};
 
 use tokio::{
-    signal,
+    signal::{
+        self,
+        unix::{self, SignalKind},
+    },
     sync::mpsc::{self, Sender},
     task::JoinSet,
+    time::{self, Instant},
 };
 use tokio_util::sync::CancellationToken;
 
 use bot_handlers::{
-    admin_command_handler, callback_handler, command_handler, message_handler,
-    run_collect_user_names_job, start_updates_notify_job, AdminCommand, Command,
+    admin_command_handler, admin_forbidden_handler, callback_handler, command_handler,
+    is_not_banned, message_handler, run_collect_user_names_job, start_backup_job,
+    start_broadcast_job, start_retention_job, start_source_health_job, start_updates_notify_job,
+    AdminCommand, BroadcastQueue, Command, SettingsStorage,
 };
-use common::{is_admin_chat_id, spawn_with_token, LogError};
-use db::DB;
+use common::{admins, has_admin_role, init_admins, is_admin_chat_id, spawn_with_token, LogError};
+use db::{SourceRepo, DB};
 use sources::spawn_sources_update_jobs;
 
 use crate::{
-    handlers::tg_logs::{start_tg_logs_job, LogMessage},
-    logger::TgLogger,
+    cli::{Cli, Command as CliCommand},
+    config::{Config, LogConfig, WebhookConfig},
+    handlers::tg_logs::{start_tg_logs_job, LogRecord},
+    health::{notify_systemd_stopping, start_systemd_notify_job, DispatcherStatus, Health},
+    http::{start_http_server, HttpState},
+    json_logger::JsonLogger,
+    logger::{TgLogger, TgLoggerHandle},
 };
 
+mod cli;
+mod config;
 mod handlers;
+mod health;
+mod http;
+mod json_logger;
 mod logger;
 
-const DB_FILE: &str = dotenv!("DB_URL");
-const LOG_CHAT_ID: &str = dotenv!("LOG_CHAT_ID");
-const BOT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
-
-const IS_REAL_PROD: bool = cfg!(feature = "prod");
-const IS_TEST_PROD: bool = cfg!(feature = "test-prod");
-const IS_PROD: bool = IS_REAL_PROD || IS_TEST_PROD;
-
-const TG_BOT_TOKEN: &str = if IS_REAL_PROD {
-    dotenv!("PROD_BOT_TOKEN")
-} else {
-    dotenv!("BOT_TOKEN")
-};
-const LOG_LEVEL: LevelFilter = if IS_REAL_PROD {
-    LevelFilter::Error
-} else {
-    LevelFilter::Debug
-};
-const TG_LOG_ENABLED: bool = IS_PROD;
+pub(crate) const BOT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Logs of these crates are too verbose, they are not written to terminal and
+/// json logs
+const NOISY_LOG_TARGETS: &[&str] = &["h2", "hyper", "reqwest", "rustls", "sqlx"];
+/// Time for jobs to finish on shutdown, should be less than systemd's
+/// `TimeoutStopSec`, which is 90s by default
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time to send logs to telegram after other jobs are finished
+const LOGS_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load().context("invalid config")?;
+    cli::execute(cli.command.unwrap_or(CliCommand::Run), config).await
+}
+
+/// Run bot with all jobs until shutdown signal
+async fn run(config: Config) -> Result<()> {
     let tg_logs_chan = mpsc::channel(100);
-    let log_chat_id = LOG_CHAT_ID.parse().ok().map(ChatId);
 
-    init_logger(tg_logs_chan.0);
+    let tg_logger = init_logger(&config.log, tg_logs_chan.0.clone())?;
+    init_panic_hook();
+    init_admins(config.admin.roles());
 
-    if IS_PROD {
-        log::info!(tg = true; "Bot started");
-    }
+    log::info!(tg = true, event = "started"; "Bot started");
 
-    let db = DB::init(&db_path()).await?;
+    log::debug!("opening {:?} db", config.db.backend);
+    let db = config.db.open().await?;
+    let settings_storage = SettingsStorage::new(db.clone());
 
     let bot = Bot::with_client(
-        TG_BOT_TOKEN,
+        &config.bot.token,
         Client::builder().timeout(BOT_REQUEST_TIMEOUT).build()?,
     );
     set_bot_commands(bot.clone()).await?;
 
     let updates_chan = mpsc::channel(100);
+    let broadcast_chan = mpsc::channel(10);
+    let fetch_status_chan = mpsc::channel(100);
     let cancel_token = CancellationToken::new();
 
+    let http_state = match &config.http {
+        Some(_) => Some(
+            HttpState::new(db.clone())?
+                .with_queue("updates", &updates_chan.0)
+                .with_queue("broadcasts", &broadcast_chan.0)
+                .with_queue("tg_logs", &tg_logs_chan.0),
+        ),
+        None => None,
+    };
+
+    // logs are flushed last, to send logs of shutdown
+    let logs_token = CancellationToken::new();
+    let mut logs_jobs = JoinSet::new();
+    // job is started anyway, log chat can be set on reload
+    logs_jobs.spawn(start_tg_logs_job(
+        bot.clone(),
+        tg_logs_chan.1,
+        logs_token.clone(),
+    ));
+    if !config.log.tg_enabled() {
+        log::warn!("log chat is not set, logs are not sent to telegram")
+    }
+
+    // jobs, which are dropped on shutdown
     let mut jobs = JoinSet::new();
-    if let Some(log_chat_id) = log_chat_id {
+    // fetched updates are counted as found only if they are newer
+    let last_updates = db
+        .select_sources()
+        .await?
+        .into_iter()
+        .map(|s| (s.name().to_string(), s.last_updated_at()))
+        .collect();
+    let sources_control = spawn_sources_update_jobs(
+        &mut jobs,
+        cancel_token.clone(),
+        updates_chan.0,
+        fetch_status_chan.0,
+        &config.sources,
+        &last_updates,
+    );
+    let dispatcher_status = DispatcherStatus::default();
+    let health = Health::new(
+        db.clone(),
+        sources_control.clone(),
+        dispatcher_status.clone(),
+    );
+    if let (Some(http), Some(state)) = (&config.http, http_state) {
         jobs.spawn(spawn_with_token(
             cancel_token.clone(),
-            start_tg_logs_job(bot.clone(), log_chat_id, tg_logs_chan.1),
+            start_http_server(http.address, state, health.clone()),
         ));
-    } else {
-        log::warn!("LOG_CHAT_ID env not set, skip starting tg logs job")
     }
     jobs.spawn(spawn_with_token(
         cancel_token.clone(),
-        start_bot(bot.clone(), db.clone()),
+        start_source_health_job(bot.clone(), db.clone(), fetch_status_chan.1),
     ));
-    spawn_sources_update_jobs(&mut jobs, cancel_token.clone(), updates_chan.0);
     jobs.spawn(spawn_with_token(
         cancel_token.clone(),
         run_collect_user_names_job(bot.clone(), db.clone()),
     ));
+    if let Some(backup) = &config.db.backup {
+        jobs.spawn(spawn_with_token(
+            cancel_token.clone(),
+            start_backup_job(
+                db.clone(),
+                PathBuf::from(&backup.dir),
+                Duration::from_secs(backup.interval_secs),
+                backup.keep,
+            ),
+        ));
+    }
+    if let Some(retention) = &config.db.retention {
+        jobs.spawn(spawn_with_token(
+            cancel_token.clone(),
+            start_retention_job(
+                db.clone(),
+                Duration::from_secs(retention.blocked_user_days * 24 * 60 * 60),
+                Duration::from_secs(retention.interval_secs),
+            ),
+        ));
+    }
+    jobs.spawn(spawn_with_token(
+        cancel_token.clone(),
+        start_systemd_notify_job(health),
+    ));
     jobs.spawn(spawn_with_token(
         cancel_token.clone(),
-        start_updates_notify_job(bot.clone(), db, updates_chan.1),
+        start_reload_job(tg_logger),
     ));
 
-    jobs.spawn(async move {
-        signal::ctrl_c()
-            .await
-            .log_error_msg("failed to listen for SIGINT");
-        cancel_token.cancel();
-    });
+    // jobs, which finish their work on shutdown. Notify job finishes, when
+    // sources are stopped, and broadcast job, when bot is stopped
+    let mut drain_jobs = JoinSet::new();
+    drain_jobs.spawn(start_bot(
+        bot.clone(),
+        dptree::deps![
+            db.clone(),
+            settings_storage,
+            BroadcastQueue::new(broadcast_chan.0),
+            sources_control,
+            dispatcher_status.clone()
+        ],
+        dispatcher_status,
+        config.webhook,
+        cancel_token.clone(),
+    ));
+    drain_jobs.spawn(start_broadcast_job(
+        bot.clone(),
+        db.clone(),
+        broadcast_chan.1,
+        cancel_token.clone(),
+    ));
+    drain_jobs.spawn(start_updates_notify_job(
+        bot.clone(),
+        db.clone(),
+        updates_chan.1,
+    ));
 
-    while (jobs.join_next().await).is_some() {}
+    shutdown_signal().await;
+    log::info!("shutting down");
+    notify_systemd_stopping();
+
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    cancel_token.cancel();
+    join_until(jobs, deadline).await;
+    join_until(drain_jobs, deadline).await;
+    db.close().await;
+    logs_token.cancel();
+    join_until(logs_jobs, Instant::now() + LOGS_FLUSH_TIMEOUT).await;
 
     Ok(())
 }
 
+/// Wait for SIGINT or SIGTERM, which is sent by systemd
+async fn shutdown_signal() {
+    let mut sigterm = match unix::signal(SignalKind::terminate()) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("failed to listen for SIGTERM: {e}");
+            signal::ctrl_c()
+                .await
+                .log_error_msg("failed to listen for SIGINT");
+            return;
+        }
+    };
+    tokio::select! {
+        res = signal::ctrl_c() => {
+            res.log_error_msg("failed to listen for SIGINT");
+        }
+        _ = sigterm.recv() => {}
+    }
+}
+
+/// On SIGHUP read config again and apply its telegram logs settings: log
+/// chat, ignored strings and routes. Other settings need restart
+async fn start_reload_job(tg_logger: TgLoggerHandle) {
+    let mut sighup = match unix::signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("failed to listen for SIGHUP: {e}");
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        match Config::load() {
+            Ok(config) => {
+                tg_logger.set_config(tg_logger_config(&config.log));
+                log::info!(tg = true, event = "config_reloaded"; "Log routes are reloaded");
+            }
+            Err(e) => log::error!("failed to reload config, keeping current one: {e}"),
+        }
+    }
+}
+
+/// Wait for jobs to finish until `deadline`, then abort remaining
+async fn join_until(mut jobs: JoinSet<()>, deadline: Instant) {
+    let join_all = async { while jobs.join_next().await.is_some() {} };
+    if time::timeout_at(deadline, join_all).await.is_err() {
+        log::error!("shutdown timed out, aborting {} jobs", jobs.len());
+        jobs.shutdown().await;
+    }
+}
+
 async fn set_bot_commands(bot: Bot) -> Result<()> {
     for lang in i18n::Localize::languages() {
         bot.set_my_commands(Command::bot_commands_translated(lang))
@@ -114,90 +282,180 @@ async fn set_bot_commands(bot: Bot) -> Result<()> {
             .await?;
     }
 
-    Ok(())
-}
+    // admins see also admin commands, available for their role. It fails
+    // for admin, who didn't start bot yet, then other admins are still set
+    'admins: for (chat_id, role) in admins() {
+        for lang in i18n::Localize::languages() {
+            let commands = Command::bot_commands_translated(lang)
+                .into_iter()
+                .chain(AdminCommand::bot_commands_translated(role, lang));
+            let res = bot
+                .set_my_commands(commands)
+                .language_code(lang)
+                .scope(BotCommandScope::Chat {
+                    chat_id: Recipient::Id(ChatId(chat_id)),
+                })
+                .await;
+            if let Err(e) = res {
+                log::warn!("failed to set commands for admin {chat_id}: {e}");
+                continue 'admins;
+            }
+        }
+    }
 
-fn db_path() -> String {
-    #[allow(clippy::const_is_empty)]
-    if DB_FILE.is_empty() {
-        panic!("DB_URL env variable is empty")
-    }
-    let db_file = if IS_REAL_PROD {
-        let home = match std::env::var("HOME") {
-            Ok(s) => s,
-            Err(_) => "/".to_string(),
-        };
-        format!("{home}/{DB_FILE}")
-    } else {
-        DB_FILE.to_string()
-    };
-    log::debug!("opening db at {db_file}");
-    db_file
+    Ok(())
 }
 
-fn init_logger(sender: Sender<LogMessage>) {
+/// Install loggers. Telegram logger is installed even without log chat, its
+/// config can be replaced with returned handle
+fn init_logger(config: &LogConfig, sender: Sender<LogRecord>) -> Result<TgLoggerHandle> {
     use simplelog::*;
 
-    use logger::{Config as TgConfig, ConfigBuilder as TgConfigBuilder};
-
-    let term_config = if IS_REAL_PROD {
-        Config::default()
-    } else {
-        ConfigBuilder::new()
-            .add_filter_ignore_str("h2")
-            .add_filter_ignore_str("hyper")
-            .add_filter_ignore_str("reqwest")
-            .add_filter_ignore_str("rustls")
-            .add_filter_ignore_str("sqlx")
-            .build()
-    };
-
-    let tg_config = if IS_REAL_PROD {
-        TgConfigBuilder::new()
-            .add_ignore("ConnectionReset")
-            .add_ignore("TerminatedByOtherGetUpdates")
-            .build()
-    } else {
-        TgConfig::default()
-    };
+    let mut term_config = ConfigBuilder::new();
+    for target in NOISY_LOG_TARGETS {
+        term_config.add_filter_ignore_str(target);
+    }
 
-    CombinedLogger::init(vec![
-        TermLogger::new(
-            LOG_LEVEL,
-            term_config,
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![];
+    // json logs to stdout replace human-readable logs
+    if config.json.as_ref().is_none_or(|c| c.path.is_some()) {
+        loggers.push(TermLogger::new(
+            config.level,
+            term_config.build(),
             TerminalMode::Mixed,
             ColorChoice::Auto,
-        ),
-        TgLogger::new(sender, tg_config),
-    ])
-    .expect("failed to init logger");
+        ));
+    }
+    if let Some(json) = &config.json {
+        let logger = JsonLogger::new(json.level, NOISY_LOG_TARGETS, json.path.as_deref())
+            .with_context(|| format!("failed to open json log file {:?}", json.path))?;
+        loggers.push(logger);
+    }
+    let tg_logger = TgLogger::new(sender, tg_logger_config(config));
+    let handle = tg_logger.handle();
+    loggers.push(tg_logger);
+
+    CombinedLogger::init(loggers).expect("failed to init logger");
+    Ok(handle)
+}
+
+fn tg_logger_config(config: &LogConfig) -> logger::Config {
+    let mut tg_config = logger::ConfigBuilder::new();
+    for s in &config.tg_ignore {
+        tg_config.add_ignore(s);
+    }
+    if let Some(chat_id) = config.chat_id {
+        tg_config.chat(chat_id);
+    }
+    for route in &config.routes {
+        tg_config.add_route(route.clone());
+    }
+    tg_config.build()
 }
 
-async fn start_bot(bot: Bot, db: DB) {
+/// Log panics, so they are sent to telegram. Panicked jobs are stopped
+/// without other notice
+fn init_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!(event = "panic"; "{info}");
+        default_hook(info);
+    }));
+}
+
+/// Run dispatcher until `token` is cancelled. `deps` should contain
+/// dependencies of all handlers
+async fn start_bot(
+    bot: Bot,
+    deps: DependencyMap,
+    dispatcher_status: DispatcherStatus,
+    webhook: Option<WebhookConfig>,
+    token: CancellationToken,
+) {
     log::debug!("starting bot");
     let handler = dptree::entry()
+        .inspect(|status: DispatcherStatus| status.on_update())
+        .filter_async(is_not_banned::<DB>)
         .branch(
             Update::filter_message()
                 .branch(
                     dptree::entry()
                         .filter_command::<AdminCommand>()
                         .filter(|msg: Message| is_admin_chat_id(msg.chat.id.0))
-                        .endpoint(admin_command_handler),
+                        .branch(
+                            dptree::filter(|msg: Message, cmd: AdminCommand| {
+                                has_admin_role(msg.chat.id.0, cmd.required_role())
+                            })
+                            .endpoint(admin_command_handler::<DB>),
+                        )
+                        .endpoint(admin_forbidden_handler::<DB>),
                 )
                 .branch(
                     dptree::entry()
                         .filter_command::<Command>()
-                        .endpoint(command_handler),
+                        .endpoint(command_handler::<DB>),
                 )
-                .endpoint(message_handler),
+                .endpoint(message_handler::<DB>),
         )
-        .branch(Update::filter_callback_query().endpoint(callback_handler));
-    Dispatcher::builder(bot.clone(), handler)
-        .dependencies(dptree::deps![db])
+        .branch(Update::filter_callback_query().endpoint(callback_handler::<DB>));
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .dependencies(deps)
         .default_handler(|update| async move { log::error!("unhandled update: {update:?}") })
         .error_handler(LoggingErrorHandler::with_custom_text("error in dispatcher"))
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    // dispatcher finishes handling of received updates before stopping. It
+    // can't be stopped, when it is not started yet, e.g. while webhook is
+    // removed, then it is dropped without waiting
+    let shutdown_token = dispatcher.shutdown_token();
+    let idle_token = CancellationToken::new();
+    tokio::spawn({
+        let idle_token = idle_token.clone();
+        async move {
+            token.cancelled().await;
+            match shutdown_token.shutdown() {
+                Ok(f) => f.await,
+                Err(e) => {
+                    log::debug!("failed to stop dispatcher: {e}");
+                    idle_token.cancel();
+                }
+            }
+        }
+    });
+
+    dispatcher_status.set_running(true);
+    let dispatch = async {
+        match webhook {
+            // polling removes webhook, if it was set before
+            None => {
+                let listener = update_listeners::polling_default(bot).await;
+                dispatcher
+                    .dispatch_with_listener(
+                        dispatcher_status.listener(listener),
+                        LoggingErrorHandler::with_custom_text("error in update listener"),
+                    )
+                    .await
+            }
+            Some(webhook) => {
+                log::debug!("setting webhook, listening on {}", webhook.address);
+                // webhook is removed, when listener is stopped
+                match webhooks::axum(bot, webhook.options()).await {
+                    Ok(listener) => {
+                        dispatcher
+                            .dispatch_with_listener(
+                                dispatcher_status.listener(listener),
+                                LoggingErrorHandler::with_custom_text("error in webhook listener"),
+                            )
+                            .await
+                    }
+                    Err(e) => log::error!("failed to set webhook: {e}"),
+                }
+            }
+        }
+    };
+    tokio::select! {
+        _ = dispatch => {}
+        _ = idle_token.cancelled() => {}
+    }
+    dispatcher_status.set_running(false);
 }